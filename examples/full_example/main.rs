@@ -2,6 +2,7 @@
 use httpserver::{
     http::{
         request::HttpError,
+        request_line::Method,
         response::{Response, StatusCode, file_response, html_response},
     },
     runtime::{
@@ -13,8 +14,8 @@ use httpserver::{
 #[tokio::main]
 async fn main() -> Result<(), HttpError> {
     let mut router = Router::new();
-    router.route("/", |_req| async {
-        file_response(StatusCode::Ok, "/static/hello.html")
+    router.route("/", |req| async move {
+        file_response(&req, StatusCode::Ok, "/static/hello.html")
             .await
             .unwrap_or_else(|_| {
                 html_response(
@@ -38,18 +39,12 @@ async fn main() -> Result<(), HttpError> {
             status: StatusCode::Ok,
             headers,
             body: body.as_bytes().to_vec(),
+            channel: None,
         }
     });
 
-    // Example POST. Since the server doesn't differentiate by method, both GET /submit and POST /submit would work.
-    router.route("/submit", |req| async move {
-        if req.request_line.method != "POST" {
-            return html_response(
-                StatusCode::BadRequest,
-                "<html><body><h1>Method Not Allowed</h1></body></html>",
-            );
-        }
-
+    // Example POST. Other methods on /submit are answered with 405 Method Not Allowed by the router.
+    router.route_method(Method::Post, "/submit", |req| async move {
         let Ok(_body) = String::from_utf8(req.body) else {
             return html_response(
                 StatusCode::BadRequest,
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+/// Who may store a response, as set by the `public` and `private` directives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Shared caches such as proxies may store the response
+    Public,
+    /// Only the cache of the client may store the response
+    Private,
+}
+
+/// The directives of a `Cache-Control` header.
+///
+/// Parses the header of requests and builds the header value of responses. Directives not covered are ignored.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use httpserver::http::cache_control::CacheControl;
+/// let cache_control = CacheControl::new().public().max_age(Duration::from_secs(3600)).immutable();
+/// assert_eq!(cache_control.header_value(), "public, max-age=3600, immutable");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// Whether the message must not be stored by any cache
+    pub no_store: bool,
+    /// Whether a stored response must be revalidated before it is used
+    pub no_cache: bool,
+    /// How long a response stays fresh
+    pub max_age: Option<Duration>,
+    /// Who may store the response, left to the cache if None
+    pub visibility: Option<Visibility>,
+    /// Whether the response never changes while it is fresh, so clients skip revalidating it
+    pub immutable: bool,
+}
+
+impl CacheControl {
+    /// Creates an empty set of directives.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the value of a `Cache-Control` header.
+    ///
+    /// Directive names are matched case-insensitively, a malformed `max-age` is ignored.
+    ///
+    /// # Examples
+    /// ```
+    /// let cache_control = httpserver::http::cache_control::CacheControl::parse("no-cache, max-age=0");
+    /// assert!(cache_control.no_cache);
+    /// assert_eq!(cache_control.max_age, Some(std::time::Duration::ZERO));
+    /// ```
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        let mut cache_control = Self::default();
+        for directive in value.split(',').map(str::trim) {
+            let (name, argument) = directive.split_once('=').unwrap_or((directive, ""));
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "max-age" => {
+                    cache_control.max_age = argument
+                        .trim()
+                        .trim_matches('"')
+                        .parse()
+                        .ok()
+                        .map(Duration::from_secs);
+                }
+                "public" => cache_control.visibility = Some(Visibility::Public),
+                "private" => cache_control.visibility = Some(Visibility::Private),
+                "immutable" => cache_control.immutable = true,
+                _ => {}
+            }
+        }
+        cache_control
+    }
+
+    /// Sets the `no-store` directive.
+    #[must_use]
+    pub const fn no_store(mut self) -> Self {
+        self.no_store = true;
+        self
+    }
+
+    /// Sets the `no-cache` directive.
+    #[must_use]
+    pub const fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Sets the `max-age` directive, sent in whole seconds.
+    #[must_use]
+    pub const fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the `public` directive, replacing `private`.
+    #[must_use]
+    pub const fn public(mut self) -> Self {
+        self.visibility = Some(Visibility::Public);
+        self
+    }
+
+    /// Sets the `private` directive, replacing `public`.
+    #[must_use]
+    pub const fn private(mut self) -> Self {
+        self.visibility = Some(Visibility::Private);
+        self
+    }
+
+    /// Sets the `immutable` directive.
+    #[must_use]
+    pub const fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    /// Formats the directives as `Cache-Control` header value.
+    #[must_use]
+    pub fn header_value(&self) -> String {
+        let mut directives = Vec::new();
+        match self.visibility {
+            Some(Visibility::Public) => directives.push("public".to_string()),
+            Some(Visibility::Private) => directives.push("private".to_string()),
+            None => {}
+        }
+        if self.no_store {
+            directives.push("no-store".to_string());
+        }
+        if self.no_cache {
+            directives.push("no-cache".to_string());
+        }
+        if let Some(max_age) = self.max_age {
+            directives.push(format!("max-age={}", max_age.as_secs()));
+        }
+        if self.immutable {
+            directives.push("immutable".to_string());
+        }
+        directives.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::http::cache_control::{CacheControl, Visibility};
+
+    #[test]
+    fn parses_request_directives() {
+        let cache_control = CacheControl::parse("No-Cache, max-age=0, x-unknown=1");
+
+        assert!(cache_control.no_cache);
+        assert!(!cache_control.no_store);
+        assert_eq!(cache_control.max_age, Some(Duration::ZERO));
+        assert_eq!(cache_control.visibility, None);
+    }
+
+    #[test]
+    fn builds_response_header_value() {
+        let cache_control = CacheControl::new()
+            .private()
+            .public()
+            .max_age(Duration::from_hours(1))
+            .immutable();
+
+        assert_eq!(cache_control.visibility, Some(Visibility::Public));
+        assert_eq!(
+            cache_control.header_value(),
+            "public, max-age=3600, immutable"
+        );
+        assert_eq!(
+            CacheControl::new().no_store().no_cache().header_value(),
+            "no-store, no-cache"
+        );
+    }
+}
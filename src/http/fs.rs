@@ -0,0 +1,433 @@
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::http::{
+    cache_control::CacheControl,
+    date::http_date,
+    range::{RangeRequest, parse_range},
+    request::{HttpError, Request},
+    request_line::Method,
+    response::{Response, StatusCode, html_response, redirect_response},
+};
+
+/// Serves the files of a directory under a URL prefix, such as `/static/app.js` from `public/app.js`.
+///
+/// Requests for a directory are answered with its `index.html`, or redirected to the path with a trailing slash if it
+/// lacks one. Paths that fail to decode or try to leave the directory are answered with `403 Forbidden`, missing files and
+/// paths outside of the prefix with `404 Not Found`.
+///
+/// # Examples
+/// ```
+/// use std::{sync::Arc, time::Duration};
+/// use httpserver::{
+///     http::{cache_control::CacheControl, fs::StaticFiles, response::{Response, StatusCode}},
+///     runtime::router::Router,
+/// };
+/// let files = Arc::new(
+///     StaticFiles::new("/static", "public")
+///         .cache_control(CacheControl::new().public().max_age(Duration::from_secs(3600))),
+/// );
+/// let mut router = Router::new();
+/// router.fallback(move |req| {
+///     let files = Arc::clone(&files);
+///     async move {
+///         files
+///             .response(&req)
+///             .await
+///             .unwrap_or_else(|_| Response::builder().status(StatusCode::InternalServerError).build())
+///     }
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticFiles {
+    /// The URL prefix the directory is served under, without a trailing slash
+    prefix: String,
+    /// The directory the files are read from
+    root: PathBuf,
+    /// The `Cache-Control` directives sent with every served file, none if None
+    cache_control: Option<CacheControl>,
+}
+
+impl StaticFiles {
+    /// Serves the passed directory under the passed URL prefix, `/` serving it at the root.
+    #[must_use]
+    pub fn new(prefix: &str, root: impl Into<PathBuf>) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            root: root.into(),
+            cache_control: None,
+        }
+    }
+
+    /// Sets the `Cache-Control` directives sent with every served file.
+    #[must_use]
+    pub const fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Answers the request with the file its path maps to, see `file_response`.
+    ///
+    /// Only `GET` and `HEAD` are allowed, other methods are answered with `405 Method Not Allowed`.
+    ///
+    /// # Errors
+    ///
+    /// Throws an `HttpError::Io` if the file exists but cannot be read.
+    pub async fn response(&self, request: &Request) -> Result<Response, HttpError> {
+        if !matches!(request.request_line.method, Method::Get | Method::Head) {
+            let html = "<html><body><h1>Method Not Allowed</h1></body></html>";
+            let mut response = html_response(StatusCode::MethodNotAllowed, html);
+            response.headers.insert("allow", "GET, HEAD");
+            return Ok(response);
+        }
+        let Some(path) = request.request_line.decoded_path() else {
+            let html = "<html><body><h1>Forbidden</h1></body></html>";
+            return Ok(html_response(StatusCode::Forbidden, html));
+        };
+        let Some(relative) = path
+            .strip_prefix(&self.prefix)
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        else {
+            let html = "<html><body><h1>Not Found</h1></body></html>";
+            return Ok(html_response(StatusCode::NotFound, html));
+        };
+
+        let mut file = self.root.join(relative.trim_start_matches('/'));
+        if tokio::fs::metadata(&file)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            // Relative links in the index resolve against the directory only if its URL ends with a slash.
+            if !path.ends_with('/') {
+                return Ok(directory_redirect(request));
+            }
+            file.push("index.html");
+        }
+        let mut response = file_response(request, &file).await?;
+        if let Some(cache_control) = self.cache_control
+            && response.status.is_success()
+        {
+            response
+                .headers
+                .insert("cache-control", cache_control.header_value());
+        }
+        Ok(response)
+    }
+}
+
+/// Creates a `301 Moved Permanently` response to the path of the request with a trailing slash, keeping the query.
+///
+/// Leading slashes are collapsed into one, as a location starting with `//` would redirect to another host.
+fn directory_redirect(request: &Request) -> Response {
+    let target = request.request_line.origin_form();
+    let location = match target.split_once('?') {
+        Some((path, query)) => format!("/{}/?{query}", path.trim_start_matches('/')),
+        None => format!("/{}/", target.trim_start_matches('/')),
+    };
+    redirect_response(StatusCode::MovedPermanently, &location)
+}
+
+/// Creates a `200 OK` response with the contents of the file at the passed path.
+///
+/// `Content-Type` is guessed from the file extension, see `content_type`, and `Last-Modified` is set from the file
+/// metadata if the platform reports it. A missing file results in a `404 Not Found` response.
+///
+/// A single range in the `Range` header of the request is answered with `206 Partial Content` containing only that
+/// range, or `416 Range Not Satisfiable` if it lies outside of the file. Multiple ranges are ignored and the full file is sent.
+///
+/// The path is used as is, paths taken from a request have to be checked first, see `RequestLine::decoded_path`.
+///
+/// # Errors
+///
+/// Throws an `HttpError::Io` if the file exists but cannot be read.
+pub async fn file_response(request: &Request, path: &Path) -> Result<Response, HttpError> {
+    file_range_response(path, request.headers.get("range")).await
+}
+
+/// Creates the response with the contents of the file at the passed path for the passed `Range` header value, see `file_response`.
+///
+/// # Errors
+///
+/// Throws an `HttpError::Io` if the file exists but cannot be read.
+pub(crate) async fn file_range_response(
+    path: &Path,
+    range: Option<&str>,
+) -> Result<Response, HttpError> {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let html = "<html><body><h1>Not Found</h1></body></html>";
+            return Ok(html_response(StatusCode::NotFound, html));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let metadata = file.metadata().await?;
+    let last_modified = metadata.modified().ok();
+
+    let total = metadata.len();
+    let range = range.map_or(RangeRequest::Full, |value| parse_range(value, total, 1));
+
+    let mut builder = Response::builder()
+        .header("content-type", content_type(path))
+        .header("accept-ranges", "bytes");
+    if let Some(last_modified) = last_modified {
+        builder = builder.header("last-modified", http_date(last_modified));
+    }
+    let response = match range {
+        RangeRequest::Full => {
+            let mut body = Vec::new();
+            file.read_to_end(&mut body).await?;
+            builder.body(body)
+        }
+        RangeRequest::Partial(ranges) => {
+            let range = ranges[0];
+            // Only the requested bytes are read, so serving a small range of a large file stays cheap.
+            file.seek(SeekFrom::Start(range.start)).await?;
+            let mut body = Vec::new();
+            file.take(range.size()).read_to_end(&mut body).await?;
+            builder
+                .status(StatusCode::PartialContent)
+                .header("content-range", range.content_range(total))
+                .body(body)
+        }
+        RangeRequest::Unsatisfiable => builder
+            .status(StatusCode::RangeNotSatisfiable)
+            .header("content-range", format!("bytes */{total}")),
+    };
+    Ok(response.build())
+}
+
+/// Guesses the media type of a file from its extension, falling back to `text/plain`.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use httpserver::http::fs::content_type;
+/// assert_eq!(content_type(Path::new("assets/video.MP4")), "video/mp4");
+/// assert_eq!(content_type(Path::new("notes")), "text/plain");
+/// ```
+#[must_use]
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "mp4" => "video/mp4",
+        "wasm" => "application/wasm",
+        "svg" => "image/svg+xml",
+        _ => "text/plain",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use crate::http::{
+        cache_control::CacheControl,
+        fs::{StaticFiles, file_response},
+        request::Request,
+        response::{Response, StatusCode},
+    };
+
+    const HELLO: &str = "examples/full_example/static/hello.html";
+
+    fn get(range: Option<&str>) -> Request {
+        let range = range.map_or_else(String::new, |range| format!("Range: {range}\r\n"));
+        Request::from_slice(
+            format!("GET /hello.html HTTP/1.1\r\nHost: localhost\r\n{range}\r\n").as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn existing_file_is_served_with_content_type() {
+        let path = Path::new(HELLO);
+
+        let response = file_response(&get(None), path).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            response.headers.get("content-length"),
+            Some(std::fs::metadata(path).unwrap().len().to_string().as_str())
+        );
+        assert!(
+            response
+                .headers
+                .get("last-modified")
+                .is_some_and(|date| date.ends_with(" GMT"))
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_found() {
+        let response = file_response(&get(None), Path::new("examples/does-not-exist.css"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn range_is_served_as_partial_content() {
+        let content = std::fs::read(HELLO).unwrap();
+        let total = content.len();
+
+        let response = file_response(&get(Some("bytes=0-9")), Path::new(HELLO))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::PartialContent);
+        assert_eq!(response.headers.get("accept-ranges"), Some("bytes"));
+        assert_eq!(
+            response.headers.get("content-range"),
+            Some(format!("bytes 0-9/{total}").as_str())
+        );
+        assert_eq!(response.headers.get("content-length"), Some("10"));
+        assert_eq!(response.body, content[..10]);
+    }
+
+    #[tokio::test]
+    async fn suffix_range_is_served_from_the_end_of_the_file() {
+        let content = std::fs::read(HELLO).unwrap();
+        let total = content.len();
+
+        let response = file_response(&get(Some("bytes=-5")), Path::new(HELLO))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::PartialContent);
+        assert_eq!(
+            response.headers.get("content-range"),
+            Some(format!("bytes {}-{}/{total}", total - 5, total - 1).as_str())
+        );
+        assert_eq!(response.body, content[total - 5..]);
+    }
+
+    #[tokio::test]
+    async fn range_outside_of_file_is_not_satisfiable() {
+        let total = std::fs::metadata(HELLO).unwrap().len();
+
+        let response = file_response(&get(Some(&format!("bytes={total}-"))), Path::new(HELLO))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::RangeNotSatisfiable);
+        assert_eq!(
+            response.headers.get("content-range"),
+            Some(format!("bytes */{total}").as_str())
+        );
+        assert!(response.body.is_empty());
+    }
+
+    /// Helper function requesting the passed target from static files served under `/static`.
+    async fn static_response(files: &StaticFiles, target: &str) -> Response {
+        let request = Request::from_slice(
+            format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes(),
+        )
+        .unwrap();
+        files.response(&request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn static_file_is_served_with_cache_control() {
+        let files = StaticFiles::new("/static/", "examples/full_example/static").cache_control(
+            CacheControl::new()
+                .public()
+                .max_age(Duration::from_secs(30)),
+        );
+
+        let response = static_response(&files, "/static/hello.html").await;
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.body, std::fs::read(HELLO).unwrap());
+        assert_eq!(
+            response.headers.get("cache-control"),
+            Some("public, max-age=30")
+        );
+    }
+
+    #[tokio::test]
+    async fn directory_request_serves_index_or_redirects_to_slash() {
+        let root = std::env::temp_dir().join(format!("httpserver-static-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/index.html"), "<h1>Docs</h1>").unwrap();
+        let files = StaticFiles::new("/static", &root);
+
+        let with_slash = static_response(&files, "/static/docs/").await;
+        let without_slash = static_response(&files, "/static/docs?lang=en").await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(with_slash.status, StatusCode::Ok);
+        assert_eq!(with_slash.body, b"<h1>Docs</h1>");
+        assert_eq!(without_slash.status, StatusCode::MovedPermanently);
+        assert_eq!(
+            without_slash.headers.get("location"),
+            Some("/static/docs/?lang=en")
+        );
+    }
+
+    #[tokio::test]
+    async fn directory_redirect_stays_on_the_same_host() {
+        let root = std::env::temp_dir().join(format!("httpserver-root-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        let files = StaticFiles::new("/", &root);
+
+        let response = static_response(&files, "//docs").await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(response.status, StatusCode::MovedPermanently);
+        assert_eq!(response.headers.get("location"), Some("/docs/"));
+    }
+
+    #[tokio::test]
+    async fn traversal_out_of_static_directory_is_forbidden() {
+        let files = StaticFiles::new("/static", "examples/full_example/static");
+
+        for target in [
+            "/static/../main.rs",
+            "/static/%2e%2e/main.rs",
+            "/static/%ZZ",
+        ] {
+            let response = static_response(&files, target).await;
+
+            assert_eq!(response.status, StatusCode::Forbidden, "{target}");
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_static_file_is_not_found() {
+        let files = StaticFiles::new("/static", "examples/full_example/static")
+            .cache_control(CacheControl::new().max_age(Duration::from_secs(30)));
+
+        for target in [
+            "/static/missing.html",
+            "/staticfiles/hello.html",
+            "/hello.html",
+        ] {
+            let response = static_response(&files, target).await;
+
+            assert_eq!(response.status, StatusCode::NotFound, "{target}");
+            assert_eq!(response.headers.get("cache-control"), None);
+        }
+    }
+}
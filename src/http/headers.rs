@@ -1,16 +1,56 @@
-use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
 
 use crate::http::request::HttpError;
 
-/// A `HashMap` of two strings representing key, value pairs used in HTTP Headers.
+/// An insertion-ordered map of two strings representing key, value pairs used in HTTP Headers.
+///
+/// Headers are iterated and written in the order they were first inserted, so the output is deterministic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Headers(IndexMap<String, HeaderValues>);
+
+/// The values of a single header, kept individually and joined with a comma.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
-pub struct Headers(HashMap<String, String>);
+struct HeaderValues {
+    /// The values in the order they were added, joined with `", "`
+    joined: String,
+    /// Every value on its own, as comma-joining is lossy for headers like `Set-Cookie`
+    values: Vec<String>,
+}
+
+impl HeaderValues {
+    /// Creates the values of a header with a single value.
+    fn single(value: String) -> Self {
+        Self {
+            joined: value.clone(),
+            values: vec![value],
+        }
+    }
+}
 
 impl Headers {
-    /// Returns a new `HashMap` constructed as a 'Headers' struct
+    /// Returns a new, empty 'Headers' struct
     #[must_use]
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self(IndexMap::new())
+    }
+
+    /// Builds a Headers struct from a slice of key / value pairs.
+    ///
+    /// Keys are stored lowercase and repeated keys are appended like during parsing.
+    ///
+    /// # Examples
+    /// ```
+    /// let headers = httpserver::http::headers::Headers::from_pairs(&[("Accept", "text/html"), ("accept", "*/*")]);
+    /// assert_eq!(headers.get("accept"), Some("text/html, */*"));
+    /// ```
+    #[must_use]
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Self {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
     }
 
     /// Inserts a new entry into the Headers struct by passing both key and value
@@ -21,7 +61,8 @@ impl Headers {
     /// headers.insert("drink", "milk");
     /// ```
     pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
-        self.0.insert(key.into(), value.into());
+        self.0
+            .insert(key.into(), HeaderValues::single(value.into()));
     }
 
     /// Retrieves the value of a specified key.
@@ -34,12 +75,87 @@ impl Headers {
     /// headers.insert("drink", "milk");
     /// assert_eq!(headers.get("drink"), Some("milk"));
     /// ```
+    #[must_use]
     pub fn get(&self, key: &str) -> Option<&str> {
-        self.0.get(key).map(String::as_str)
+        self.0.get(key).map(|values| values.joined.as_str())
+    }
+
+    /// Retrieves every value of a specified key separately, in the order they were added.
+    ///
+    /// Yields nothing if the specified key was not found in the header.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut headers = httpserver::http::headers::Headers::new();
+    /// headers.append("set-cookie", "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT");
+    /// headers.append("set-cookie", "b=2");
+    /// let cookies: Vec<&str> = headers.get_all("set-cookie").collect();
+    /// assert_eq!(cookies, ["a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT", "b=2"]);
+    /// ```
+    pub fn get_all(&self, key: &str) -> impl Iterator<Item = &str> + '_ {
+        self.0
+            .get(key)
+            .into_iter()
+            .flat_map(|values| values.values.iter().map(String::as_str))
+    }
+
+    /// Retrieves the value of a specified key as a list of comma separated tokens, such as `Accept-Encoding` or `Connection`.
+    ///
+    /// Repeated headers are split like a single one, empty list elements are skipped and whitespace around tokens is trimmed.
+    /// Returns an empty list if the specified key was not found in the header.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut headers = httpserver::http::headers::Headers::new();
+    /// headers.append("accept-encoding", "gzip, deflate");
+    /// headers.append("accept-encoding", "br");
+    /// assert_eq!(headers.get_list("accept-encoding"), ["gzip", "deflate", "br"]);
+    /// ```
+    #[must_use]
+    pub fn get_list(&self, key: &str) -> Vec<&str> {
+        self.get(key)
+            .into_iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .collect()
+    }
+
+    /// Whether an entry for the specified key exists.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut headers = httpserver::http::headers::Headers::new();
+    /// headers.insert("drink", "milk");
+    /// assert!(headers.contains_key("drink"));
+    /// assert!(!headers.contains_key("food"));
+    /// ```
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Removes the entry of the specified key, returning its value. The remaining entries keep their order.
+    ///
+    /// The key is lowercased like during parsing, so `remove("Content-Length")` removes a parsed `content-length` header.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut headers = httpserver::http::headers::Headers::new();
+    /// headers.insert("drink", "milk");
+    /// assert_eq!(headers.remove("Drink"), Some("milk".to_string()));
+    /// assert_eq!(headers.get("drink"), None);
+    /// ```
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0
+            .shift_remove(&key.to_lowercase())
+            .map(|values| values.joined)
     }
 
     /// Appends a key / value pair into the Header.
     ///
+    /// `get` returns repeated values joined with a comma, `get_all` returns them separately.
+    ///
     /// # Examples
     /// ```
     /// let mut headers = httpserver::http::headers::Headers::new();
@@ -54,20 +170,31 @@ impl Headers {
         let value = value.into();
 
         if let Some(existing) = self.0.get_mut(&key) {
-            if !existing.is_empty() {
-                existing.push_str(", ");
+            if !existing.joined.is_empty() {
+                existing.joined.push_str(", ");
             }
-            existing.push_str(&value);
+            existing.joined.push_str(&value);
+            existing.values.push(value);
         } else {
-            self.0.insert(key, value);
+            self.0.insert(key, HeaderValues::single(value));
         }
     }
 
-    /// Implements an iterator for the Header
+    /// Implements an iterator for the Header, yielding the entries in insertion order with repeated values joined
     pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
         self.0
             .iter()
-            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .map(|(key, values)| (key.as_str(), values.joined.as_str()))
+    }
+
+    /// Iterates over every value separately, yielding a repeated key once per value, as written to the wire.
+    pub fn iter_all(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.0.iter().flat_map(|(key, values)| {
+            values
+                .values
+                .iter()
+                .map(move |value| (key.as_str(), value.as_str()))
+        })
     }
 
     /// Returns the length of the Headers, that being the raw amount of entries.
@@ -82,21 +209,129 @@ impl Headers {
         self.len() == 0
     }
 
+    /// Whether the `content-type` header declares a text-like media type.
+    ///
+    /// Covers `text/*`, JSON, XML, JavaScript and form data including structured syntax suffixes like `+json`. Returns false without a `content-type` header.
+    ///
+    /// # Examples
+    /// ```
+    /// let headers = httpserver::http::headers::Headers::from_pairs(&[("content-type", "application/json; charset=utf-8")]);
+    /// assert!(headers.is_text_content());
+    /// ```
+    #[must_use]
+    pub fn is_text_content(&self) -> bool {
+        const TEXT_TYPES: [&str; 6] = [
+            "application/json",
+            "application/xml",
+            "application/javascript",
+            "application/ecmascript",
+            "application/x-www-form-urlencoded",
+            "image/svg+xml",
+        ];
+
+        let Some(media_type) = self.media_type() else {
+            return false;
+        };
+
+        media_type.starts_with("text/")
+            || TEXT_TYPES.contains(&media_type.as_str())
+            || media_type.ends_with("+json")
+            || media_type.ends_with("+xml")
+    }
+
+    /// Whether the `content-type` header declares a media type that is already compressed.
+    ///
+    /// Covers images except SVG, audio, video and archive formats. Returns false without a `content-type` header.
+    ///
+    /// # Examples
+    /// ```
+    /// let headers = httpserver::http::headers::Headers::from_pairs(&[("content-type", "image/png")]);
+    /// assert!(headers.is_compressed_content());
+    /// ```
+    #[must_use]
+    pub fn is_compressed_content(&self) -> bool {
+        const COMPRESSED_TYPES: [&str; 8] = [
+            "application/zip",
+            "application/gzip",
+            "application/x-gzip",
+            "application/x-bzip2",
+            "application/x-7z-compressed",
+            "application/vnd.rar",
+            "application/zstd",
+            "font/woff2",
+        ];
+
+        let Some(media_type) = self.media_type() else {
+            return false;
+        };
+
+        (media_type.starts_with("image/") && media_type != "image/svg+xml")
+            || media_type.starts_with("audio/")
+            || media_type.starts_with("video/")
+            || COMPRESSED_TYPES.contains(&media_type.as_str())
+    }
+
+    /// Returns the lowercase media type of the `content-type` header without its parameters.
+    fn media_type(&self) -> Option<String> {
+        let content_type = self.get("content-type")?;
+        Some(
+            content_type
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase(),
+        )
+    }
+
+    /// Collapses a `content-length` repeated with identical values into a single value, as allowed by RFC 9110.
+    ///
+    /// Differing values are kept, so `duplicate_headers` still rejects them.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut headers = httpserver::http::headers::Headers::from_pairs(&[("Content-Length", "5"), ("Content-Length", "5")]);
+    /// headers.collapse_content_length();
+    /// assert_eq!(headers.get("content-length"), Some("5"));
+    /// ```
+    pub fn collapse_content_length(&mut self) {
+        let Some(existing) = self.0.get_mut("content-length") else {
+            return;
+        };
+        let mut values = existing.joined.split(',').map(str::trim);
+        let first = values.next().unwrap_or_default().to_string();
+        if values.all(|other| other == first) {
+            *existing = HeaderValues::single(first);
+        }
+    }
+
     /// Validates critical headers not appearing multiple times.
     #[must_use]
     pub fn duplicate_headers(&self) -> bool {
+        self.duplicate_critical_header().is_some()
+    }
+
+    /// Returns the name of the first critical header appearing multiple times.
+    ///
+    /// Separate header lines count, while a single line listing several values like `Connection: keep-alive, Upgrade` is
+    /// accepted. The exception is `Host`, which names a single authority, so `Host: a, b` is reported as well.
+    ///
+    /// # Examples
+    /// ```
+    /// let headers = httpserver::http::headers::Headers::from_pairs(&[("Host", "a"), ("Host", "b")]);
+    /// assert_eq!(headers.duplicate_critical_header(), Some("host"));
+    /// ```
+    #[must_use]
+    pub fn duplicate_critical_header(&self) -> Option<&'static str> {
         const CRITICAL_HEADERS: [&str; 4] =
             ["host", "content-length", "transfer-encoding", "connection"];
 
-        for header in CRITICAL_HEADERS {
-            if let Some(value) = self.get(header)
-                && value.contains(", ")
-            {
-                return true;
-            }
+        if self.get_list("host").len() > 1 {
+            return Some("host");
         }
-
-        false
+        CRITICAL_HEADERS
+            .into_iter()
+            .find(|header| self.get_all(header).count() > 1)
     }
 
     /// Parses passed data from a byte array to a header.
@@ -114,50 +349,46 @@ impl Headers {
     {
         // size of \r\n fixed as 2
         const CRLF_LEN: usize = 2;
-        let string = String::from_utf8_lossy(data.as_ref());
+        let data = data.as_ref();
         let mut line_length = 0;
 
-        if string.find("\r\n\r\n").is_some() {
-            let headers = string.split("\r\n");
-            for header in headers {
-                if header.is_empty() {
-                    line_length += CRLF_LEN; //There is still one linebreak left here, the one separating headers from body
-                    break;
-                }
-                line_length += header.len() + CRLF_LEN;
-                self.create_header_from_string(header)?;
+        // Lines are measured in raw bytes, so invalid UTF-8 replaced for parsing cannot shift the amount of data consumed.
+        while let Some(line_feed) = data[line_length..].iter().position(|byte| *byte == b'\n') {
+            // A bare LF is not accepted as line ending, as a proxy splitting lines differently could be smuggled a request.
+            if line_feed == 0 || data[line_length + line_feed - 1] != b'\r' {
+                return Err(HttpError::MalformedHeader {
+                    offset: line_length + line_feed,
+                });
             }
-            return Ok((line_length, true));
-        }
-
-        if string.find("\r\n").is_some()
-            && let Some((base, _end)) = string.rsplit_once("\r\n")
-        {
-            for line in base.split("\r\n") {
-                if line.is_empty() {
-                    line_length += CRLF_LEN; //There is still one linebreak left here, the one separating headers from body
-                    return Ok((line_length, true));
-                }
-                line_length += line.len() + CRLF_LEN;
-                self.create_header_from_string(line)?;
+            let line_end = line_feed - 1;
+            if line_end == 0 {
+                line_length += CRLF_LEN; //There is still one linebreak left here, the one separating headers from body
+                return Ok((line_length, true));
             }
-            return Ok((line_length, false));
+            let line = String::from_utf8_lossy(&data[line_length..line_length + line_end]);
+            self.create_header_from_string(&line)
+                .map_err(|e| e.shifted(line_length))?;
+            line_length += line_end + CRLF_LEN;
         }
-        Ok((0, false))
+        Ok((line_length, false))
     }
 
+    /// Parses a single header line, reporting the offset within the line if it is malformed.
+    ///
+    /// A line starting with whitespace is rejected, as obsolete line folding continuing the previous header is deprecated by RFC 9112.
     fn create_header_from_string(&mut self, string: &str) -> Result<(), HttpError> {
-        let trim = string.trim();
-        let result = trim.split_once(':').ok_or(HttpError::MalformedHeader);
+        if string.starts_with([' ', '\t']) {
+            return Err(HttpError::MalformedHeader { offset: 0 });
+        }
+        let result = string
+            .trim_end()
+            .split_once(':')
+            .ok_or(HttpError::MalformedHeader { offset: 0 });
         let (key, mut value) = result?;
         value = value.trim();
 
-        if key.contains(' ') {
-            return Err(HttpError::MalformedHeader);
-        }
-
-        if !key.chars().all(is_valid_char) {
-            return Err(HttpError::MalformedHeader);
+        if let Some(position) = key.find(|c| !is_valid_char(c)) {
+            return Err(HttpError::MalformedHeader { offset: position });
         }
 
         let key_lowercase = key.to_lowercase();
@@ -176,6 +407,48 @@ impl Headers {
     }
 }
 
+/// Renders one header per line as `Name: value` with canonical casing, sorted by name for stable log output.
+impl fmt::Display for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<(&str, &str)> = self.iter().collect();
+        entries.sort_unstable();
+        for (index, (key, value)) in entries.into_iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {value}", canonical_name(key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Capitalizes every `-` separated part of a header name, as in `Content-Type`.
+fn canonical_name(key: &str) -> String {
+    key.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect()
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+impl FromIterator<(String, String)> for Headers {
+    /// Collects key / value pairs into Headers, lowercasing keys and appending repeated ones.
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut headers = Self::new();
+        for (key, value) in iter {
+            headers.append(key.to_lowercase(), value);
+        }
+        headers
+    }
+}
+
 /// Helper method to determine whether the passed character is valid according to <https://www.rfc-editor.org/rfc/rfc9110#section-5.6.2>
 const fn is_valid_char(c: char) -> bool {
     if c.is_ascii_alphanumeric() {
@@ -207,7 +480,7 @@ mod tests {
 
     #[test]
     fn single_header_extra_whitespace_valid() {
-        let input = "        Host: localhost:8080\r\n\r\n             ";
+        let input = "Host: localhost:8080        \r\n\r\n             ";
         let mut headers = Headers::new();
         let result = headers.parse_header(input);
         assert!(result.is_ok());
@@ -220,7 +493,7 @@ mod tests {
 
     #[test]
     fn single_header_extra_whitespace_value_valid() {
-        let input = "        HoSt:    localhost:8080\r\n\r\n             ";
+        let input = "HoSt:    localhost:8080        \r\n\r\n             ";
         let mut headers = Headers::new();
         let result = headers.parse_header(input);
         assert!(result.is_ok());
@@ -259,10 +532,13 @@ mod tests {
 
     #[test]
     fn invalid_spacing_headers_should_throw_malformedheader() {
-        let input = "          Host : localhost:8080          \r\n\r\n";
+        let input = "Host : localhost:8080          \r\n\r\n";
         let mut headers = Headers::new();
         let result = headers.parse_header(input);
-        assert!(matches!(result, Err(HttpError::MalformedHeader)));
+        assert!(matches!(
+            result,
+            Err(HttpError::MalformedHeader { offset: 4 })
+        ));
     }
 
     #[test]
@@ -313,4 +589,172 @@ mod tests {
         assert_eq!(size, 24);
         assert!(done);
     }
+
+    #[test]
+    fn from_pairs_lowercases_and_appends_duplicates() {
+        let headers = Headers::from_pairs(&[
+            ("Host", "localhost:8080"),
+            ("Accept", "text/html"),
+            ("ACCEPT", "application/json"),
+        ]);
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("host").unwrap(), "localhost:8080");
+        assert_eq!(
+            headers.get("accept").unwrap(),
+            "text/html, application/json"
+        );
+    }
+
+    #[test]
+    fn malformed_header_reports_offset() {
+        let input = "Host: localhost:8080\r\nBad Header: value\r\n\r\n";
+        let mut headers = Headers::new();
+        let result = headers.parse_header(input);
+        assert!(matches!(
+            result,
+            Err(HttpError::MalformedHeader { offset: 25 })
+        ));
+    }
+
+    #[test]
+    fn text_content_types_are_classified() {
+        let text = [
+            "text/html",
+            "text/plain; charset=utf-8",
+            "Application/JSON",
+            "application/xml",
+            "application/javascript",
+            "application/problem+json",
+            "image/svg+xml",
+        ];
+        let binary = [
+            "image/png",
+            "application/octet-stream",
+            "application/pdf",
+            "video/mp4",
+        ];
+
+        for content_type in text {
+            let headers = Headers::from_pairs(&[("content-type", content_type)]);
+            assert!(headers.is_text_content(), "{content_type}");
+        }
+        for content_type in binary {
+            let headers = Headers::from_pairs(&[("content-type", content_type)]);
+            assert!(!headers.is_text_content(), "{content_type}");
+        }
+        assert!(!Headers::new().is_text_content());
+    }
+
+    #[test]
+    fn display_renders_canonical_lines() {
+        let headers = Headers::from_pairs(&[
+            ("content-type", "text/html"),
+            ("X-REQUEST-ID", "42"),
+            ("host", "localhost:8080"),
+        ]);
+
+        assert_eq!(
+            headers.to_string(),
+            "Content-Type: text/html\nHost: localhost:8080\nX-Request-Id: 42"
+        );
+        assert_eq!(Headers::new().to_string(), "");
+    }
+
+    #[test]
+    fn identical_content_lengths_are_collapsed() {
+        let mut headers = Headers::new();
+        headers
+            .parse_header("Content-Length: 5\r\nContent-Length: 5\r\n\r\n")
+            .unwrap();
+
+        headers.collapse_content_length();
+
+        assert_eq!(headers.get("content-length"), Some("5"));
+        assert!(!headers.duplicate_headers());
+    }
+
+    #[test]
+    fn differing_content_lengths_are_rejected() {
+        let mut headers = Headers::new();
+        headers
+            .parse_header("Content-Length: 5\r\nContent-Length: 6\r\n\r\n")
+            .unwrap();
+
+        headers.collapse_content_length();
+
+        assert_eq!(headers.get("content-length"), Some("5, 6"));
+        assert!(headers.duplicate_headers());
+    }
+
+    #[test]
+    fn comma_separated_host_is_rejected() {
+        let mut headers = Headers::new();
+        headers
+            .parse_header("Host: a.example, b.example\r\nConnection: keep-alive, Upgrade\r\n\r\n")
+            .unwrap();
+
+        assert_eq!(headers.duplicate_critical_header(), Some("host"));
+
+        headers.insert("host", "a.example");
+        assert_eq!(headers.duplicate_critical_header(), None);
+    }
+
+    #[test]
+    fn remove_finds_parsed_header_regardless_of_case() {
+        let mut headers = Headers::new();
+        headers
+            .parse_header("Connection: close\r\nContent-Length: 5\r\n\r\n")
+            .unwrap();
+
+        assert_eq!(headers.remove("Content-Length"), Some("5".to_string()));
+        assert!(!headers.contains_key("content-length"));
+        assert!(headers.contains_key("connection"));
+    }
+
+    #[test]
+    fn remove_missing_header_returns_none() {
+        let mut headers = Headers::new();
+        headers.parse_header("Connection: close\r\n\r\n").unwrap();
+
+        assert_eq!(headers.remove("keep-alive"), None);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn get_list_splits_token_lists() {
+        let mut headers = Headers::new();
+        headers
+            .parse_header("Accept-Encoding: gzip, deflate\r\nTE: trailers\r\nConnection: keep-alive,\r\nConnection: ,Upgrade\r\n\r\n")
+            .unwrap();
+
+        assert_eq!(headers.get_list("accept-encoding"), ["gzip", "deflate"]);
+        assert_eq!(headers.get_list("te"), ["trailers"]);
+        assert_eq!(headers.get_list("connection"), ["keep-alive", "Upgrade"]);
+        assert!(headers.get_list("cache-control").is_empty());
+    }
+
+    #[test]
+    fn folded_header_should_throw_malformedheader() {
+        let input = "Host: localhost:8080\r\nX-Folded: first\r\n second\r\n\r\n";
+        let mut headers = Headers::new();
+        let result = headers.parse_header(input);
+
+        assert!(matches!(
+            result,
+            Err(HttpError::MalformedHeader { offset: 39 })
+        ));
+    }
+
+    #[test]
+    fn bare_lf_terminated_header_should_throw_malformedheader() {
+        let input = "Host: localhost:8080\nX-Smuggled: yes\r\n\r\n";
+        let mut headers = Headers::new();
+        let result = headers.parse_header(input);
+
+        assert!(matches!(
+            result,
+            Err(HttpError::MalformedHeader { offset: 20 })
+        ));
+    }
 }
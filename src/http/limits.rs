@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+/// Limits applied while parsing a single request.
+///
+/// The defaults match the defaults of the server configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The size limit in bytes for the entire request
+    pub max_request_size: usize,
+    /// The size limit in bytes for the request line and headers
+    pub max_header_size: usize,
+    /// The maximum amount of headers allowed per request
+    pub max_header_count: usize,
+    /// The size limit in bytes for a single chunk of a chunked request body
+    pub max_chunk_size: usize,
+    /// The timeout for a single read while parsing
+    pub read_timeout: Duration,
+    /// The timeout for receiving the request line and all headers, starting with the first byte of the request
+    pub header_timeout: Duration,
+    /// The amount of body bytes kept in memory before the rest is written to a temporary file, None keeps the entire body in memory
+    pub body_spill_threshold: Option<usize>,
+    /// The maximum amount of bytes requested from the stream per read
+    ///
+    /// Reads start with `INITIAL_READ_BUFFER_SIZE` bytes and double up to this size while they fill the buffer.
+    pub read_buffer_size: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_request_size: 16 * 1024 * 1024,
+            max_header_size: 32 * 1024,
+            max_header_count: 72,
+            max_chunk_size: 1024 * 1024,
+            read_timeout: Duration::from_secs(30),
+            header_timeout: Duration::from_secs(10),
+            body_spill_threshold: None,
+            read_buffer_size: 8 * 1024,
+        }
+    }
+}
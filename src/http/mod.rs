@@ -1,8 +1,26 @@
+/// Module containing the directives of `Cache-Control` headers
+pub mod cache_control;
+/// Module containing the formatting of HTTP dates
+pub mod date;
+/// Module containing the serving of files
+pub mod fs;
 /// Module containing logic to parse HTTP headers
 pub mod headers;
+/// Module containing the limits applied while parsing requests
+pub mod limits;
+/// Module containing logic to parse query strings
+pub mod query;
+/// Module containing logic to parse `Range` headers
+pub mod range;
 /// Module containing logic to parse requests
 pub mod request;
 /// Module containing logic to parse HTTP request lines
 pub mod request_line;
 /// Module formatting the response.
 pub mod response;
+/// Module containing the temporary storage of large request bodies
+pub mod spill;
+/// Module containing the writer for Server-Sent Events
+pub mod sse;
+/// Module containing the WebSocket opening handshake
+pub mod websocket;
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+/// A `HashMap` of the decoded key, value pairs of a request target's query string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query(HashMap<String, String>);
+
+impl Query {
+    /// Parses a raw query string, the part of the request target after the first `?`.
+    ///
+    /// Keys and values are percent-decoded with `+` read as a space. Malformed percent sequences are kept as is and invalid UTF-8 is decoded lossily.
+    /// Keys without a value map to an empty string, repeated keys are joined with a comma like headers.
+    ///
+    /// # Examples
+    /// ```
+    /// let query = httpserver::http::query::Query::parse("q=rust+http&page=2&flag");
+    /// assert_eq!(query.get("q"), Some("rust http"));
+    /// assert_eq!(query.get("flag"), Some(""));
+    /// ```
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        let mut query = Self::default();
+        for pair in raw.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            query.append(percent_decode(key), percent_decode(value));
+        }
+        query
+    }
+
+    /// Retrieves the decoded value of a specified key.
+    ///
+    /// Returns None if the key is not part of the query.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Implements an iterator for the Query
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Returns the amount of distinct keys in the Query.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the Query contains no keys.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Appends a key / value pair, joining repeated keys with a comma.
+    fn append(&mut self, key: String, value: String) {
+        if let Some(existing) = self.0.get_mut(&key) {
+            existing.push_str(", ");
+            existing.push_str(&value);
+        } else {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// Decodes `%XX` sequences and `+` as used in `application/x-www-form-urlencoded` data.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = byte {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                decoded.push(b'%');
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::query::Query;
+
+    #[test]
+    fn pairs_are_percent_decoded() {
+        let query = Query::parse("q=caf%C3%A9+au+lait&sort=%3Cnew%3E");
+
+        assert_eq!(query.get("q"), Some("café au lait"));
+        assert_eq!(query.get("sort"), Some("<new>"));
+        assert_eq!(query.len(), 2);
+    }
+
+    #[test]
+    fn empty_values_and_repeated_keys() {
+        let query = Query::parse("flag&tag=a&tag=b&&empty=");
+
+        assert_eq!(query.get("flag"), Some(""));
+        assert_eq!(query.get("empty"), Some(""));
+        assert_eq!(query.get("tag"), Some("a, b"));
+    }
+
+    #[test]
+    fn malformed_percent_sequences_decode_lossily() {
+        let query = Query::parse("a=%zz&b=100%&c=%4&d=%FF&e=%+4");
+
+        assert_eq!(query.get("a"), Some("%zz"));
+        assert_eq!(query.get("b"), Some("100%"));
+        assert_eq!(query.get("c"), Some("%4"));
+        assert_eq!(query.get("d"), Some("\u{FFFD}"));
+        assert_eq!(query.get("e"), Some("% 4"));
+    }
+}
@@ -0,0 +1,213 @@
+/// The amount of ranges a single `Range` header may request before it is ignored.
+pub const DEFAULT_MAX_RANGES: usize = 16;
+
+/// A range of bytes of a representation, both ends inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    /// The offset of the first byte in the range
+    pub start: u64,
+    /// The offset of the last byte in the range
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Returns the amount of bytes covered by the range.
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Formats the value of the `Content-Range` header for this range of a representation with the passed length.
+    ///
+    /// # Examples
+    /// ```
+    /// let range = httpserver::http::range::ByteRange { start: 0, end: 1023 };
+    /// assert_eq!(range.content_range(4096), "bytes 0-1023/4096");
+    /// ```
+    #[must_use]
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{total}", self.start, self.end)
+    }
+}
+
+/// How a `Range` header applies to a representation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// The header is absent, malformed or requests too many ranges, so the full representation is sent
+    Full,
+    /// The satisfiable ranges, in the order they were requested
+    Partial(Vec<ByteRange>),
+    /// None of the requested ranges overlap the representation, to be answered with `416 Range Not Satisfiable`
+    Unsatisfiable,
+}
+
+/// Parses the value of a `Range` header against a representation of `len` bytes.
+///
+/// Supports `bytes=start-end`, `bytes=start-` and `bytes=-suffix` specs separated by commas.
+/// A header with more than `max_ranges` specs is ignored like a malformed one, so a client cannot force the server
+/// to assemble a response out of thousands of tiny ranges.
+///
+/// # Examples
+/// ```
+/// use httpserver::http::range::{ByteRange, RangeRequest, parse_range};
+/// assert_eq!(
+///     parse_range("bytes=-100", 1000, 1),
+///     RangeRequest::Partial(vec![ByteRange { start: 900, end: 999 }])
+/// );
+/// assert_eq!(parse_range("bytes=0-1,2-3", 1000, 1), RangeRequest::Full);
+/// ```
+#[must_use]
+pub fn parse_range(value: &str, len: u64, max_ranges: usize) -> RangeRequest {
+    let Some((unit, specs)) = value.trim().split_once('=') else {
+        return RangeRequest::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return RangeRequest::Full;
+    }
+
+    let specs: Vec<&str> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect();
+    if specs.is_empty() || specs.len() > max_ranges {
+        return RangeRequest::Full;
+    }
+
+    let mut ranges = Vec::with_capacity(specs.len());
+    for spec in specs {
+        match parse_spec(spec, len) {
+            Some(Spec::Overlapping(range)) => ranges.push(range),
+            Some(Spec::Outside) => {}
+            None => return RangeRequest::Full,
+        }
+    }
+
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(ranges)
+    }
+}
+
+/// A syntactically valid range spec, relative to the representation.
+enum Spec {
+    /// The spec covers the contained bytes of the representation
+    Overlapping(ByteRange),
+    /// The spec does not overlap the representation
+    Outside,
+}
+
+/// Parses a single range spec.
+///
+/// Returns None if the spec is malformed.
+fn parse_spec(spec: &str, len: u64) -> Option<Spec> {
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        let suffix = parse_position(last)?;
+        if suffix == 0 || len == 0 {
+            return Some(Spec::Outside);
+        }
+        return Some(Spec::Overlapping(ByteRange {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        }));
+    }
+
+    let start = parse_position(first)?;
+    let end = if last.is_empty() {
+        u64::MAX
+    } else {
+        parse_position(last)?
+    };
+    if end < start {
+        return None;
+    }
+    if start >= len {
+        return Some(Spec::Outside);
+    }
+    Some(Spec::Overlapping(ByteRange {
+        start,
+        end: end.min(len - 1),
+    }))
+}
+
+/// Parses a byte position, which may only consist of digits.
+fn parse_position(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use crate::http::range::{ByteRange, DEFAULT_MAX_RANGES, RangeRequest, parse_range};
+
+    #[test]
+    fn single_ranges_are_clamped_to_the_representation() {
+        assert_eq!(
+            parse_range("bytes=0-1023", 4096, DEFAULT_MAX_RANGES),
+            RangeRequest::Partial(vec![ByteRange {
+                start: 0,
+                end: 1023
+            }])
+        );
+        assert_eq!(
+            parse_range("bytes=4000-", 4096, DEFAULT_MAX_RANGES),
+            RangeRequest::Partial(vec![ByteRange {
+                start: 4000,
+                end: 4095
+            }])
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 4096, DEFAULT_MAX_RANGES),
+            RangeRequest::Partial(vec![ByteRange {
+                start: 0,
+                end: 4095
+            }])
+        );
+        assert_eq!(
+            parse_range("bytes=5000-", 4096, DEFAULT_MAX_RANGES),
+            RangeRequest::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn malformed_ranges_fall_back_to_full_representation() {
+        for value in [
+            "items=0-1",
+            "bytes=",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=+1-2",
+        ] {
+            assert_eq!(
+                parse_range(value, 100, DEFAULT_MAX_RANGES),
+                RangeRequest::Full,
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_ranges_fall_back_to_full_representation() {
+        let mut value = String::from("bytes=0-0");
+        for i in 1..=DEFAULT_MAX_RANGES {
+            write!(value, ",{}-{}", i * 2, i * 2).unwrap();
+        }
+
+        assert_eq!(
+            parse_range(&value, 1000, DEFAULT_MAX_RANGES),
+            RangeRequest::Full
+        );
+        assert!(matches!(
+            parse_range(&value, 1000, DEFAULT_MAX_RANGES + 1),
+            RangeRequest::Partial(ranges) if ranges.len() == DEFAULT_MAX_RANGES + 1
+        ));
+    }
+}
@@ -1,19 +1,30 @@
-use std::time::Duration;
+use std::collections::HashMap;
 
+use base64::{Engine, engine::general_purpose::STANDARD};
 use config::ConfigError;
+use md5::Md5;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use tokio::{
-    io::{AsyncRead, AsyncReadExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, join, sink},
     task::JoinError,
-    time::timeout,
+    time::{Instant, timeout_at},
 };
 
 use crate::{
     http::{
+        cache_control::CacheControl,
         headers::Headers,
+        limits::ParseLimits,
         request_line::{RequestLine, parse_request_line},
+        response::{StatusCode, write_chunked_body, write_final_body_chunk, write_status_line},
+        spill::{BodyReader, SpillFile},
+    },
+    runtime::{
+        context::RequestContext,
+        memory::{MemoryBudget, MemoryReservation},
+        server::Settings,
     },
-    runtime::server::Settings,
 };
 
 /// Representation of a HTTP request with request line, headers and body
@@ -27,8 +38,20 @@ pub struct Request {
     pub request_line: RequestLine,
     /// A custom struct representing a list of headers.
     pub headers: Headers,
-    /// The response body (can be empty).
+    /// The request body (can be empty).
+    ///
+    /// Only holds the first `body_spill_threshold` bytes if the rest of the body was spilled to disk, see `body_reader`.
     pub body: Vec<u8>,
+    /// Information about the connection the request arrived on.
+    pub context: RequestContext,
+    /// The values of the `:name` segments of the matched route pattern, filled by the router.
+    pub path_params: HashMap<String, String>,
+    /// The share of the global memory budget held by the body until the request is dropped.
+    reservation: Option<MemoryReservation>,
+    /// The part of the body exceeding the spill threshold, removed from disk once the request is dropped.
+    spill: Option<SpillFile>,
+    /// Whether the headers announced a body using `content-length` or `transfer-encoding`.
+    has_body: bool,
 }
 
 /// Represents the different stages of the parser.
@@ -40,6 +63,8 @@ enum ParseState {
     ParseHeaders,
     /// The parser is parsing the body.
     ParseBody,
+    /// The parser is parsing the trailer section following a chunked body.
+    ParseTrailers,
     /// The parser finished parsing.
     Done,
 }
@@ -60,12 +85,22 @@ pub enum HttpError {
     InvalidParserState,
 
     /// The request line does not follow the RFC standard.
-    #[error("request line is malformed")]
-    MalformedRequestLine,
+    #[error("request line is malformed at byte {offset}")]
+    MalformedRequestLine {
+        /// The offset into the request at which parsing failed.
+        offset: usize,
+    },
+
+    /// The status line of a parsed response is malformed or contains an unknown status code.
+    #[error("status line is malformed: {0}")]
+    MalformedStatusLine(String),
 
     /// The header does not follow the RFC standard.
-    #[error("header is malformed")]
-    MalformedHeader,
+    #[error("header is malformed at byte {offset}")]
+    MalformedHeader {
+        /// The offset into the request at which parsing failed.
+        offset: usize,
+    },
 
     /// The parser unexpectedly reached an end of file.
     #[error("unexpected end of file")]
@@ -75,10 +110,18 @@ pub enum HttpError {
     #[error("body length does not match header")]
     InvalidBodyLength,
 
+    /// The `Content-Length` header is not a single decimal value, such as `+5` or conflicting repeated values.
+    #[error("invalid content length")]
+    InvalidContentLength,
+
     /// There was a generic IO error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// The body is not valid UTF-8.
+    #[error("invalid UTF-8: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+
     /// There was an error parsing an integer to a string.
     #[error("Parsing error: {0}")]
     ParseError(#[from] std::num::ParseIntError),
@@ -110,61 +153,264 @@ pub enum HttpError {
     #[error("Invalid Headers")]
     InvalidHeaders,
 
+    /// A header determining the routing or framing of the request, such as `Host` or `Content-Length`, appears more than once.
+    #[error("duplicate critical header: {0}")]
+    DuplicateCriticalHeader(&'static str),
+
+    /// The request declares both `Transfer-Encoding` and `Content-Length`, which RFC 9112 requires to be rejected.
+    #[error("request declares both transfer-encoding and content-length")]
+    ConflictingFraming,
+
+    /// The TLS handshake with the client failed.
+    #[error("TLS handshake failed: {0}")]
+    TlsHandshake(#[source] std::io::Error),
+
+    /// The connection negotiated an application protocol the server does not speak.
+    #[error("unsupported application protocol: {0}")]
+    UnsupportedProtocol(String),
+
+    /// Buffering the body would exceed the memory budget shared by all requests.
+    #[error("memory limit for request bodies exceeded")]
+    MemoryLimitExceeded,
+
+    /// The request is not a valid WebSocket opening handshake.
+    #[error("invalid WebSocket handshake: {0}")]
+    InvalidWebSocketHandshake(&'static str),
+
+    /// The request contains an `Expect` header the server cannot meet.
+    #[error("Expectation failed")]
+    ExpectationFailed,
+
+    /// A response declares a transfer-encoding that does not end in `chunked`, leaving its body unframed.
+    #[error("unsupported response transfer-encoding: {0}")]
+    UnsupportedTransferEncoding(String),
+
     /// There was an error reading the config file.
     #[error("Config Error")]
     ConfigError(#[from] ConfigError),
 }
 
+impl HttpError {
+    /// Moves the offset of a malformed request line or header by the passed amount of bytes.
+    ///
+    /// Used to turn an offset relative to the parsed data into one relative to the start of the request.
+    #[must_use]
+    pub(crate) fn shifted(self, by: usize) -> Self {
+        match self {
+            Self::MalformedRequestLine { offset } => Self::MalformedRequestLine {
+                offset: offset + by,
+            },
+            Self::MalformedHeader { offset } => Self::MalformedHeader {
+                offset: offset + by,
+            },
+            other => other,
+        }
+    }
+}
+
 /// Parses the contents of a reader to a Request
 ///
 /// The reader may be of any type that implements `Read`
 ///
+/// As the reader cannot be written to, `Expect: 100-continue` is validated but never answered.
+///
 /// # Errors
 ///
 /// Throws a `HttpError` if the request was not valid.
 ///
 /// This is related to the parsed data from the buffer containing RFC-incompatible formatting.
+///
+/// Throws `HttpError::Timeout` if a read exceeds the parsing timeout or the request line and headers are not complete within the header timeout after the first byte.
 pub async fn request_from_reader<R: AsyncRead + Unpin>(
     reader: &mut R,
     settings: &Settings,
 ) -> Result<Request, HttpError> {
-    let request_timeout_value = settings.parsing_timeout;
-    let read_request_timeout = Duration::from_secs(request_timeout_value);
+    request_from_reader_with_limits(reader, &settings.parse_limits()).await
+}
+
+/// Parses the contents of a reader to a Request using the passed limits instead of the server settings.
+///
+/// # Errors
+///
+/// Throws a `HttpError` if the request was not valid or exceeds one of the limits, see `request_from_reader`.
+pub async fn request_from_reader_with_limits<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    limits: &ParseLimits,
+) -> Result<Request, HttpError> {
+    single_request(&mut join(reader, sink()), limits, None).await
+}
+
+/// Parses the contents of a stream to a Request
+///
+/// Behaves like `request_from_reader`, but answers `Expect: 100-continue` with an interim `100 Continue` response once the headers were accepted.
+///
+/// The body following the interim response is read with the same parsing timeout, so a client that never sends it does not stall the connection.
+///
+/// # Errors
+///
+/// Throws a `HttpError` if the request was not valid.
+///
+/// Throws `HttpError::ContentTooLarge` before the body is read if the declared `Content-Length` exceeds the size limit.
+pub async fn request_from_stream<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    settings: &Settings,
+) -> Result<Request, HttpError> {
+    single_request(stream, &settings.parse_limits(), None).await
+}
+
+/// Parses the contents of a stream to a Request, reserving the body size from the passed memory budget.
+///
+/// The declared `Content-Length` is reserved once the headers are parsed, before any interim response or body is read.
+/// A chunked body grows its reservation as chunks are decoded. The reservation is released when the request is dropped.
+///
+/// # Errors
+///
+/// Throws `HttpError::MemoryLimitExceeded` if the body does not fit into the remaining budget, otherwise behaves like `request_from_stream`.
+pub async fn request_from_stream_with_budget<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    settings: &Settings,
+    budget: &MemoryBudget,
+) -> Result<Request, HttpError> {
+    single_request(stream, &settings.parse_limits(), Some(budget)).await
+}
 
-    let request_size_value = settings.request_size_limit_in_mib;
-    let max_request_size = request_size_value * 1024 * 1024;
+/// Parses the next request of a connection that may carry several pipelined requests.
+///
+/// Parsing starts with the bytes in `pending`. Bytes received after the end of the request are left in `pending` for the next call.
+///
+/// Once the headers are parsed, `body_limit` is asked for a limit of the body below the request size limit, such as
+/// the one of the route the request is dispatched to. It applies before `Expect: 100-continue` is answered.
+///
+/// The request line is stored in `parsed_line` along with the time it was parsed, so a request rejected afterwards can still be reported.
+///
+/// # Errors
+///
+/// Throws a `HttpError` if the request was not valid, see `request_from_stream_with_budget`.
+pub(crate) async fn request_from_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    settings: &Settings,
+    budget: &MemoryBudget,
+    pending: &mut Vec<u8>,
+    body_limit: &BodyLimit<'_>,
+    parsed_line: &mut Option<(RequestLine, Instant)>,
+) -> Result<Request, HttpError> {
+    parse_request(
+        stream,
+        &settings.parse_limits(),
+        Some(budget),
+        pending,
+        body_limit,
+        parsed_line,
+    )
+    .await
+}
 
-    let header_size_value = settings.header_size_limit_in_kib;
-    let max_header_size = header_size_value * 1024;
+/// Resolves the body size limit of a request from its request line, None keeping the request size limit.
+pub(crate) type BodyLimit<'a> = dyn Fn(&RequestLine) -> Option<usize> + Sync + 'a;
 
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut temp = [0u8; 64];
-    let request_line = RequestLine {
-        method: String::new(),
-        request_target: String::new(),
-        http_version: String::new(),
-    };
-    let headers = Headers::new();
-    let body = Vec::new();
-    let mut request = Request {
-        parse_state: ParseState::Initialized,
-        request_line,
-        headers,
-        body,
-    };
-    let mut bytes_read = 0;
-    let mut total_bytes_read = 0;
-    let mut header_bytes_read = 0;
+/// Parses a request that is expected to be the only data on the stream.
+///
+/// # Errors
+///
+/// Throws `HttpError::InvalidBodyLength` if a body with a declared content-length was followed by more data, otherwise behaves like `parse_request`.
+async fn single_request<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    limits: &ParseLimits,
+    budget: Option<&MemoryBudget>,
+) -> Result<Request, HttpError> {
+    let mut pending = Vec::new();
+    let request = parse_request(stream, limits, budget, &mut pending, &|_| None, &mut None).await?;
+    if !pending.is_empty() && request.headers.get("content-length").is_some() {
+        return Err(HttpError::InvalidBodyLength);
+    }
+    Ok(request)
+}
+
+/// The amount of bytes requested by the first read of every request, see `ParseLimits::read_buffer_size`.
+pub const INITIAL_READ_BUFFER_SIZE: usize = 1024;
+
+/// Shared parsing loop of the `request_from_*` functions.
+///
+/// Starts with the bytes in `pending` and leaves any bytes received after the end of the request in it. The request line
+/// is stored in `parsed_line` once it is parsed.
+///
+/// The read buffer starts at `INITIAL_READ_BUFFER_SIZE` and doubles whenever a read fills it, up to `read_buffer_size`.
+/// Small requests therefore keep a small buffer, while large bodies are read in fewer calls. Every request starts small
+/// again, so a keep-alive connection does not hold on to the buffer of an earlier large request.
+async fn parse_request<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    limits: &ParseLimits,
+    budget: Option<&MemoryBudget>,
+    pending: &mut Vec<u8>,
+    body_limit: &BodyLimit<'_>,
+    parsed_line: &mut Option<(RequestLine, Instant)>,
+) -> Result<Request, HttpError> {
+    let read_request_timeout = limits.read_timeout;
+    let header_timeout = limits.header_timeout;
+    // Starts with the first received byte, so waiting for a request on an idle connection is not limited by it.
+    let mut header_deadline: Option<Instant> = None;
+
+    let max_request_size = limits.max_request_size;
+    let max_header_size = limits.max_header_size;
+
+    let mut buffer: Vec<u8> = std::mem::take(pending);
+    // Start of the bytes in `buffer` that are not parsed yet. Parsed bytes are only removed once they make up
+    // more than half of the buffer, so parsing a large header block in small reads stays linear.
+    let mut start = 0;
+    let max_read_size = limits.read_buffer_size.max(1);
+    let mut temp = vec![0u8; INITIAL_READ_BUFFER_SIZE.min(max_read_size)];
+    let mut request = Request::initialized();
+    let mut bytes_read = buffer.len();
+    let mut total_bytes_read = bytes_read;
+    if !buffer.is_empty() {
+        header_deadline = Some(Instant::now() + header_timeout);
+    }
+    let mut expectation_answered = false;
+    let mut max_body_size = max_request_size;
+    let mut consumed = 0;
 
     loop {
-        let result = timeout(read_request_timeout, async {
+        let mut deadline = Instant::now() + read_request_timeout;
+        if let Some(header_deadline) = header_deadline
+            && request.parsing_head()
+        {
+            deadline = deadline.min(header_deadline);
+        }
+
+        let result = timeout_at(deadline, async {
             match request.parse_state {
                 ParseState::Done => return Ok(true),
-                ParseState::Initialized | ParseState::ParseHeaders | ParseState::ParseBody => {
-                    let parsed = request.parse(&buffer[..bytes_read], settings)?;
+                ParseState::Initialized
+                | ParseState::ParseHeaders
+                | ParseState::ParseBody
+                | ParseState::ParseTrailers => {
+                    let parsing_head = request.parsing_head();
+                    let parsed = request
+                        .parse(&buffer[start..], limits)
+                        .map_err(|e| e.shifted(consumed))?;
                     if parsed > 0 {
-                        buffer.drain(0..parsed);
+                        consumed += parsed;
+                        if parsing_head && consumed > max_header_size {
+                            return Err(HttpError::ContentTooLarge);
+                        }
+                        start += parsed;
                         bytes_read -= parsed;
+                        if start > buffer.len() / 2 {
+                            buffer.drain(..start);
+                            start = 0;
+                        }
+                        if parsing_head && !request.parsing_head() {
+                            max_body_size = body_limit(&request.request_line)
+                                .map_or(max_request_size, |limit| limit.min(max_request_size));
+                            request.validate_declared_length(max_body_size)?;
+                        }
+                        request.store_body(limits, budget, max_body_size).await?;
+                        if request.parse_state == ParseState::ParseBody && !expectation_answered {
+                            expectation_answered = true;
+                            request
+                                .answer_expectation(stream, max_body_size, bytes_read)
+                                .await?;
+                        }
                         return Ok(false);
                     }
 
@@ -172,7 +418,12 @@ pub async fn request_from_reader<R: AsyncRead + Unpin>(
                         return Ok(true);
                     }
 
-                    let read = reader.read(&mut temp[0..]).await?;
+                    // Nothing was parsed, so while in the header section all buffered bytes belong to it.
+                    if request.parsing_head() && consumed + bytes_read > max_header_size {
+                        return Err(HttpError::ContentTooLarge);
+                    }
+
+                    let read = read_growing(stream, &mut temp, max_read_size, &mut buffer).await?;
                     if read == 0 {
                         if matches!(request.parse_state, ParseState::Done) {
                             return Ok(true);
@@ -180,29 +431,26 @@ pub async fn request_from_reader<R: AsyncRead + Unpin>(
                         return Err(HttpError::UnexpectedEOF);
                     }
 
-                    if matches!(request.parse_state, ParseState::ParseHeaders) {
-                        header_bytes_read += read;
-                    }
-
-                    buffer.extend_from_slice(&temp[0..read]);
+                    header_deadline.get_or_insert_with(|| Instant::now() + header_timeout);
                     bytes_read += read;
                     total_bytes_read += read;
 
                     if total_bytes_read > max_request_size {
                         return Err(HttpError::ContentTooLarge);
                     }
-
-                    if header_bytes_read > max_header_size {
-                        return Err(HttpError::ContentTooLarge);
-                    }
                 }
             }
             Ok(false)
         })
         .await;
+        request.report_request_line(parsed_line);
 
         match result {
-            Ok(Ok(true)) => return Ok(request),
+            Ok(Ok(true)) => {
+                buffer.drain(..start);
+                *pending = buffer;
+                return Ok(request);
+            }
             Ok(Ok(false)) => {}
             Err(_) => return Err(HttpError::Timeout),
             Ok(Err(e)) => return Err(e),
@@ -210,36 +458,479 @@ pub async fn request_from_reader<R: AsyncRead + Unpin>(
     }
 }
 
+/// Reads once from the stream into `temp`, appending the received bytes to `buffer`.
+///
+/// Doubles `temp` up to `max_read_size` if the read filled it. Returns the amount of bytes read.
+///
+/// # Errors
+///
+/// Throws an `HttpError::Io` if reading from the stream fails.
+async fn read_growing<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    temp: &mut Vec<u8>,
+    max_read_size: usize,
+    buffer: &mut Vec<u8>,
+) -> Result<usize, HttpError> {
+    let read = stream.read(temp).await?;
+    buffer.extend_from_slice(&temp[..read]);
+    if read == temp.len() && temp.len() < max_read_size {
+        temp.resize((temp.len() * 2).min(max_read_size), 0);
+    }
+    Ok(read)
+}
+
 impl Request {
+    /// Creates an empty request at the start of parsing.
+    fn initialized() -> Self {
+        Self {
+            parse_state: ParseState::Initialized,
+            request_line: RequestLine::default(),
+            headers: Headers::new(),
+            body: Vec::new(),
+            context: RequestContext::default(),
+            path_params: HashMap::new(),
+            reservation: None,
+            spill: None,
+            has_body: false,
+        }
+    }
+
+    /// Stores the request line in `parsed_line` with the current time, once it is parsed and unless it is stored already.
+    fn report_request_line(&self, parsed_line: &mut Option<(RequestLine, Instant)>) {
+        if parsed_line.is_none() && self.parse_state != ParseState::Initialized {
+            *parsed_line = Some((self.request_line.clone(), Instant::now()));
+        }
+    }
+
+    /// Whether the request line or headers are still being parsed.
+    const fn parsing_head(&self) -> bool {
+        matches!(
+            self.parse_state,
+            ParseState::Initialized | ParseState::ParseHeaders
+        )
+    }
+
+    /// Whether the request was sent with body framing, even if the body is empty.
+    ///
+    /// A request with `Content-Length: 0` has a body, one without `content-length` or `transfer-encoding` has none.
+    #[must_use]
+    pub const fn has_body(&self) -> bool {
+        self.has_body
+    }
+
+    /// Parses a request that is already completely in memory, without an async runtime.
+    ///
+    /// Runs the same state machine as the `request_from_*` functions using the default `ParseLimits`, except for the timeouts.
+    /// An `Expect: 100-continue` header is validated but never answered.
+    ///
+    /// # Examples
+    /// ```
+    /// let request = httpserver::http::request::Request::from_slice(b"GET /coffee HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    /// assert_eq!(request.request_line.path(), "/coffee");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::UnexpectedEOF` if the data ends before the request is complete and `HttpError::InvalidBodyLength`
+    /// if data follows a body with a declared content-length. Otherwise behaves like `request_from_reader`.
+    pub fn from_slice(data: &[u8]) -> Result<Self, HttpError> {
+        let limits = ParseLimits::default();
+        if data.len() > limits.max_request_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+
+        let mut request = Self::initialized();
+        let mut consumed = 0;
+        while request.parse_state != ParseState::Done {
+            let parsing_head = request.parsing_head();
+            let parsed = request
+                .parse(&data[consumed..], &limits)
+                .map_err(|e| e.shifted(consumed))?;
+            consumed += parsed;
+            if parsing_head && consumed > limits.max_header_size {
+                return Err(HttpError::ContentTooLarge);
+            }
+            if parsing_head && request.parse_state == ParseState::ParseBody {
+                request.validate_expectation(limits.max_request_size)?;
+            }
+            if parsed == 0 && request.parse_state != ParseState::Done {
+                return Err(HttpError::UnexpectedEOF);
+            }
+        }
+
+        if consumed < data.len() && request.headers.get("content-length").is_some() {
+            return Err(HttpError::InvalidBodyLength);
+        }
+        Ok(request)
+    }
+
+    /// Whether the request has been parsed completely.
+    ///
+    /// Requests returned by the `request_from_*` functions are always complete.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.parse_state == ParseState::Done
+    }
+
+    /// Retrieves the value of a path parameter extracted by the router, such as `id` for the pattern `/users/:id`.
+    ///
+    /// Returns None if the matched route has no parameter with that name.
+    #[must_use]
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.path_params.get(name).map(String::as_str)
+    }
+
+    /// Returns the scheme the client used to send the request, `https` or `http`.
+    ///
+    /// If the context trusts a proxy, a valid `X-Forwarded-Proto` header takes precedence over whether the connection uses TLS.
+    #[must_use]
+    pub fn scheme(&self) -> &'static str {
+        let forwarded = self
+            .headers
+            .get("x-forwarded-proto")
+            .filter(|_| self.context.trust_forwarded_proto)
+            .and_then(|proto| proto.split(',').next())
+            .map(str::trim);
+        match forwarded {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+            Some(proto) if proto.eq_ignore_ascii_case("http") => "http",
+            _ if self.context.is_secure => "https",
+            _ => "http",
+        }
+    }
+
+    /// Returns the absolute URI the client requested, built from the scheme, the `Host` header and the origin-form target.
+    ///
+    /// Returns None if the request has no `Host` header.
+    #[must_use]
+    pub fn effective_uri(&self) -> Option<String> {
+        let host = self.headers.get("host")?;
+        Some(format!(
+            "{}://{host}{}",
+            self.scheme(),
+            self.request_line.origin_form()
+        ))
+    }
+
+    /// Whether the body is text-like according to its `content-type` header, see `Headers::is_text_content`.
+    #[must_use]
+    pub fn is_text_content(&self) -> bool {
+        self.headers.is_text_content()
+    }
+
+    /// Returns the directives of the `Cache-Control` header, or None if the request has none.
+    #[must_use]
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        self.headers.get("cache-control").map(CacheControl::parse)
+    }
+
+    /// Returns the body as a UTF-8 string if it is at most `max` bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the body exceeds `max` bytes or was spilled to disk and `HttpError::InvalidUtf8` if it is not valid UTF-8.
+    pub fn text_limited(&self, max: usize) -> Result<String, HttpError> {
+        if self.body_len() > max || self.is_spilled() {
+            return Err(HttpError::ContentTooLarge);
+        }
+        Ok(std::str::from_utf8(&self.body)?.to_string())
+    }
+
+    /// Verifies the body against a `Content-MD5` or `Digest: sha-256=...` header.
+    ///
+    /// Returns true if the digest matches or no verifiable digest is present, false on a mismatch.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::InvalidHeaders` if the `Digest` header is malformed.
+    pub fn verify_digest(&self) -> Result<bool, HttpError> {
+        if let Some(expected) = self.headers.get("content-md5") {
+            let digest = STANDARD.encode(self.body_digest::<Md5>()?);
+            return Ok(digest == expected.trim());
+        }
+
+        if let Some(digests) = self.headers.get("digest") {
+            for entry in digests.split(',') {
+                let (algorithm, expected) = entry
+                    .trim()
+                    .split_once('=')
+                    .ok_or(HttpError::InvalidHeaders)?;
+                if algorithm.eq_ignore_ascii_case("sha-256") {
+                    let digest = STANDARD.encode(self.body_digest::<Sha256>()?);
+                    return Ok(digest == expected.trim());
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Computes the digest of the entire body, reading a spilled part from disk in blocks.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` if the spill file cannot be read.
+    fn body_digest<D: Digest>(&self) -> Result<Vec<u8>, HttpError> {
+        let mut hasher = D::new();
+        hasher.update(&self.body);
+        if let Some(spill) = &self.spill {
+            let mut file = std::fs::File::open(spill.path())?;
+            let mut block = [0u8; 8192];
+            loop {
+                let read = std::io::Read::read(&mut file, &mut block)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&block[..read]);
+            }
+        }
+        Ok(hasher.finalize().to_vec())
+    }
+
+    /// Returns the length of the entire body, including a part spilled to disk.
+    #[must_use]
+    pub fn body_len(&self) -> usize {
+        self.body.len() + self.spill.as_ref().map_or(0, SpillFile::len)
+    }
+
+    /// Whether the body exceeded the spill threshold and is partly stored in a temporary file.
+    #[must_use]
+    pub const fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Returns a reader over the entire body, reading the in-memory part first and a spilled part from disk afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` if the spill file cannot be opened.
+    pub async fn body_reader(&self) -> Result<BodyReader<'_>, HttpError> {
+        Ok(BodyReader::new(&self.body, self.spill.as_ref()).await?)
+    }
+
+    /// Checks the body received so far against `max_body_size`, then spills and reserves it as configured.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the body exceeds `max_body_size`, otherwise see `spill_body` and `reserve_body`.
+    async fn store_body(
+        &mut self,
+        limits: &ParseLimits,
+        budget: Option<&MemoryBudget>,
+        max_body_size: usize,
+    ) -> Result<(), HttpError> {
+        if self.body_len() > max_body_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+        if let Some(threshold) = limits.body_spill_threshold {
+            self.spill_body(threshold).await?;
+        }
+        if let Some(budget) = budget {
+            self.reserve_body(budget, limits)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the part of the buffered body exceeding `threshold` bytes to the spill file, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` if the spill file cannot be created or written.
+    async fn spill_body(&mut self, threshold: usize) -> Result<(), HttpError> {
+        if self.body.len() <= threshold {
+            return Ok(());
+        }
+        let spill = match &mut self.spill {
+            Some(spill) => spill,
+            None => self.spill.insert(SpillFile::create().await?),
+        };
+        spill.append(&self.body[threshold..]).await?;
+        self.body.truncate(threshold);
+        Ok(())
+    }
+
+    /// Takes the memory reservation of the body, letting the caller hold it beyond the lifetime of the request.
+    pub(crate) const fn take_reservation(&mut self) -> Option<MemoryReservation> {
+        self.reservation.take()
+    }
+
+    /// Grows the memory reservation to cover the declared and the already buffered body.
+    ///
+    /// Does nothing until the headers are parsed. It does not reserve more than the spill threshold, as the rest of the body
+    /// is written to disk.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::MemoryLimitExceeded` if the budget is exhausted.
+    fn reserve_body(
+        &mut self,
+        budget: &MemoryBudget,
+        limits: &ParseLimits,
+    ) -> Result<(), HttpError> {
+        if self.parsing_head() {
+            return Ok(());
+        }
+
+        let declared = match self.headers.get("content-length") {
+            Some(content) if !self.is_chunked() => content.parse()?,
+            _ => 0,
+        };
+        let bytes = declared
+            .min(limits.body_spill_threshold.unwrap_or(usize::MAX))
+            .max(self.body.len());
+
+        let reserved = if let Some(reservation) = &mut self.reservation {
+            reservation.grow_to(bytes)
+        } else {
+            self.reservation = budget.try_reserve(bytes);
+            self.reservation.is_some()
+        };
+        if !reserved {
+            return Err(HttpError::MemoryLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Serializes the request to the passed writer in HTTP/1.1 format.
+    ///
+    /// A chunked body is decoded during parsing, so it is encoded as chunks again to match its `transfer-encoding` header.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an `HttpError::Io` if any write operation to the underlying writer fails.
+    pub async fn write<W: AsyncWrite + Unpin>(&self, mut writer: W) -> Result<(), HttpError> {
+        let line = format!(
+            "{} {} HTTP/{}\r\n",
+            self.request_line.method,
+            self.request_line.request_target,
+            self.request_line.http_version
+        );
+        writer.write_all(line.as_bytes()).await?;
+
+        for (key, value) in self.headers.iter_all() {
+            writer
+                .write_all(format!("{key}: {value}\r\n").as_bytes())
+                .await?;
+        }
+        writer.write_all(b"\r\n").await?;
+
+        if self.is_chunked() {
+            if !self.body.is_empty() {
+                write_chunked_body(&mut writer, &self.body).await?;
+            }
+            if let Some(spill) = &self.spill {
+                let mut file = tokio::fs::File::open(spill.path()).await?;
+                let mut block = vec![0u8; 8192];
+                loop {
+                    let read = file.read(&mut block).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    write_chunked_body(&mut writer, &block[..read]).await?;
+                }
+            }
+            write_final_body_chunk(&mut writer, None).await?;
+        } else {
+            tokio::io::copy(&mut self.body_reader().await?, &mut writer).await?;
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Answers an `Expect` header once the headers are parsed, before any of the body is read.
+    ///
+    /// The interim `100 Continue` is omitted if parts of the body were already received or no body is announced.
+    /// A chunked body has no declared size, so it is accepted here and bounded by the chunk and request size limits while decoding.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the declared body exceeds the size limit and `HttpError::ExpectationFailed` for unknown expectations.
+    async fn answer_expectation<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        max_body_size: usize,
+        buffered: usize,
+    ) -> Result<(), HttpError> {
+        if self.validate_expectation(max_body_size)? && buffered == 0 {
+            write_status_line(&mut *writer, StatusCode::Continue).await?;
+            writer.write_all(b"\r\n").await?;
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Validates that the declared `Content-Length`, if any, does not exceed the passed limit.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the declared body exceeds the limit.
+    fn validate_declared_length(&self, max_body_size: usize) -> Result<(), HttpError> {
+        let declared = self.headers.get("content-length").map(str::parse::<usize>);
+        if declared.is_some_and(|length| length.is_ok_and(|length| length > max_body_size)) {
+            return Err(HttpError::ContentTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Validates an `Expect` header, returning whether the client waits for a `100 Continue` before sending the body.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the declared body exceeds the size limit and `HttpError::ExpectationFailed` for unknown expectations.
+    fn validate_expectation(&self, max_body_size: usize) -> Result<bool, HttpError> {
+        let Some(expect) = self.headers.get("expect") else {
+            return Ok(false);
+        };
+
+        if !expect.eq_ignore_ascii_case("100-continue") {
+            return Err(HttpError::ExpectationFailed);
+        }
+
+        if let Some(content) = self.headers.get("content-length") {
+            if content.parse::<usize>()? > max_body_size {
+                return Err(HttpError::ContentTooLarge);
+            }
+            Ok(true)
+        } else {
+            Ok(self.is_chunked())
+        }
+    }
+
     /// Parses passed byte data.
     ///
-    /// Returns the size of the parsed data.
+    /// Returns the size of the parsed data. Once the request is done, nothing is parsed and any passed data is left to the
+    /// caller, as it belongs to the next request of the connection.
     ///
     /// # Errors
     ///
     /// Throws an `HttpError` if the parsing fails.
     ///
     /// This is related to the parsed data from the buffer containing RFC-incompatible formatting.
-    fn parse(&mut self, data: &[u8], settings: &Settings) -> Result<usize, HttpError> {
-        let max_header_size = settings.max_header_size;
+    fn parse(&mut self, data: &[u8], limits: &ParseLimits) -> Result<usize, HttpError> {
+        const CRLF_LEN: usize = 2;
+        let max_header_size = limits.max_header_count;
 
-        let string = String::from_utf8_lossy(data);
         let mut total_size = 0;
+        // Only the request line and headers are interpreted as text, the body is kept as raw bytes.
         match self.parse_state {
             ParseState::Initialized => {
-                let (request_line_result, request_line_size) = parse_request_line(string.as_ref())?;
-                if let Some(request_line) = request_line_result {
-                    if request_line.http_version != "1.1" {
-                        return Err(HttpError::UnsupportedVersion(request_line.http_version));
-                    }
-                    self.parse_state = ParseState::ParseHeaders;
-                    self.request_line = request_line;
+                let Some(line_end) = find_crlf(data) else {
+                    return Ok(0);
+                };
+                let line = String::from_utf8_lossy(&data[..line_end + CRLF_LEN]);
+                let (request_line_result, _) = parse_request_line(&line)?;
+                let Some(request_line) = request_line_result else {
+                    return Ok(0);
+                };
+                if request_line.http_version != "1.1" {
+                    return Err(HttpError::UnsupportedVersion(request_line.http_version));
                 }
-                total_size = request_line_size;
-                Ok(total_size)
+                self.parse_state = ParseState::ParseHeaders;
+                self.request_line = request_line;
+                Ok(line_end + CRLF_LEN)
             }
             ParseState::ParseHeaders => {
-                let (header_size, done) = self.headers.parse_header(string.as_bytes())?;
+                let (header_size, done) = self.headers.parse_header(data)?;
 
                 total_size += header_size;
                 if done {
@@ -251,15 +942,33 @@ impl Request {
                         return Err(HttpError::InvalidHeaders);
                     }
 
-                    if self.headers.duplicate_headers() {
-                        return Err(HttpError::InvalidHeaders);
+                    self.headers.collapse_content_length();
+                    if let Some(length) = self.headers.get("content-length")
+                        && !is_valid_content_length(length)
+                    {
+                        return Err(HttpError::InvalidContentLength);
+                    }
+                    if let Some(header) = self.headers.duplicate_critical_header() {
+                        return Err(HttpError::DuplicateCriticalHeader(header));
+                    }
+                    // Proxies disagreeing on which of both frames the body is a request smuggling vector.
+                    if self.headers.contains_key("transfer-encoding")
+                        && self.headers.contains_key("content-length")
+                    {
+                        return Err(HttpError::ConflictingFraming);
                     }
 
+                    self.has_body =
+                        self.is_chunked() || self.headers.get("content-length").is_some();
                     self.parse_state = ParseState::ParseBody;
                 }
                 Ok(total_size)
             }
             ParseState::ParseBody => {
+                if self.is_chunked() {
+                    return self.parse_chunk(data, limits.max_chunk_size);
+                }
+
                 let Some(content) = self.headers.get("content-length") else {
                     self.parse_state = ParseState::Done;
                     return Ok(total_size);
@@ -267,52 +976,167 @@ impl Request {
 
                 let content_length: usize = content.parse()?;
 
-                let already_received = self.body.len();
+                let already_received = self.body_len();
                 if already_received > content_length {
                     return Err(HttpError::InvalidBodyLength);
                 }
 
-                let remaining = content_length.saturating_sub(self.body.len());
+                let remaining = content_length.saturating_sub(already_received);
                 let to_take = remaining.min(data.len());
 
-                if to_take < data.len() {
-                    return Err(HttpError::InvalidBodyLength);
-                }
-
                 self.body.extend_from_slice(&data[..to_take]);
 
-                if self.body.len() < content_length {
+                if self.body_len() < content_length {
                     return Ok(to_take);
                 }
 
                 self.parse_state = ParseState::Done;
                 Ok(to_take)
             }
-            ParseState::Done => {
-                if !data.is_empty() {
-                    return Err(HttpError::InvalidBodyLength);
+            ParseState::ParseTrailers => {
+                let Some(line_end) = find_crlf(data) else {
+                    return Ok(0);
+                };
+                if line_end == 0 {
+                    self.parse_state = ParseState::Done;
+                } else {
+                    self.merge_trailer(&data[..line_end + CRLF_LEN], max_header_size)?;
                 }
-                Ok(0)
+                Ok(line_end + CRLF_LEN)
             }
+            ParseState::Done => Ok(0),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        fmt::Write,
-        pin::Pin,
+    /// Parses a single trailer line following a chunked body and merges it into the headers.
+    ///
+    /// Fields that determine the framing or routing of the request are ignored, as they cannot change once the body was read.
+    ///
+    /// # Errors
+    ///
+    /// Throws an `HttpError` if the trailer is malformed or the merged headers exceed `max_header_count`.
+    fn merge_trailer(&mut self, line: &[u8], max_header_count: usize) -> Result<(), HttpError> {
+        const FORBIDDEN_TRAILERS: [&str; 5] = [
+            "host",
+            "content-length",
+            "transfer-encoding",
+            "connection",
+            "trailer",
+        ];
+
+        let mut trailer = Headers::new();
+        trailer.parse_header(line)?;
+        for (key, value) in trailer.iter() {
+            if !FORBIDDEN_TRAILERS.contains(&key) {
+                self.headers.append(key, value);
+            }
+        }
+
+        if self.headers.len() > max_header_count {
+            return Err(HttpError::InvalidHeaders);
+        }
+        Ok(())
+    }
+
+    /// Whether the body is sent using `Transfer-Encoding: chunked`.
+    fn is_chunked(&self) -> bool {
+        self.headers
+            .get("transfer-encoding")
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    }
+
+    /// Parses a single chunk of a body sent with `Transfer-Encoding: chunked`.
+    ///
+    /// Returns 0 until the size line and the entire chunk data are available.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::ContentTooLarge` if the declared chunk size exceeds `max_chunk_size`, before any of its data is buffered.
+    ///
+    /// Throws `HttpError::InvalidBodyLength` if the chunk size is invalid, see `parse_chunk_size`, or the chunk data is
+    /// not followed by a line break.
+    fn parse_chunk(&mut self, data: &[u8], max_chunk_size: usize) -> Result<usize, HttpError> {
+        const CRLF_LEN: usize = 2;
+        let Some(line_end) = find_crlf(data) else {
+            return Ok(0);
+        };
+
+        let chunk_size = parse_chunk_size(&String::from_utf8_lossy(&data[..line_end]))?;
+
+        if chunk_size > max_chunk_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+
+        if chunk_size == 0 {
+            self.parse_state = ParseState::ParseTrailers;
+            return Ok(line_end + CRLF_LEN);
+        }
+
+        let chunk_start = line_end + CRLF_LEN;
+        let chunk_end = chunk_start + chunk_size;
+        if data.len() < chunk_end + CRLF_LEN {
+            return Ok(0);
+        }
+
+        if &data[chunk_end..chunk_end + CRLF_LEN] != b"\r\n" {
+            return Err(HttpError::InvalidBodyLength);
+        }
+
+        self.body.extend_from_slice(&data[chunk_start..chunk_end]);
+        Ok(chunk_end + CRLF_LEN)
+    }
+}
+
+/// Parses the size of a chunk from its size line, ignoring chunk extensions after a `;`.
+///
+/// # Errors
+///
+/// Throws `HttpError::InvalidBodyLength` if the size is empty or contains anything but hexadecimal digits, such as a sign,
+/// and `HttpError::ParseError` if it does not fit into a `usize`.
+pub(crate) fn parse_chunk_size(size_line: &str) -> Result<usize, HttpError> {
+    let size = size_line.split(';').next().unwrap_or_default().trim();
+    if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(HttpError::InvalidBodyLength);
+    }
+    Ok(usize::from_str_radix(size, 16)?)
+}
+
+/// Whether the passed `Content-Length` value is a single decimal number without sign that fits into a `usize`.
+///
+/// Repeated values that differ remain comma-separated after collapsing, so they are rejected as well.
+fn is_valid_content_length(value: &str) -> bool {
+    !value.is_empty()
+        && value.bytes().all(|byte| byte.is_ascii_digit())
+        && value.parse::<usize>().is_ok()
+}
+
+/// Helper function returning the position of the first `\r\n` in the passed data.
+pub(crate) fn find_crlf(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|window| window == b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::Write,
+        pin::Pin,
         task::{Context, Poll},
         time::Duration,
     };
 
     use config::{Config, File};
-    use tokio::io::AsyncWriteExt;
     use tokio::io::{self, AsyncRead, BufReader, ReadBuf};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     use crate::{
-        http::request::{HttpError, request_from_reader},
+        http::limits::ParseLimits,
+        http::request::{
+            HttpError, INITIAL_READ_BUFFER_SIZE, ParseState, Request, request_from_connection,
+            request_from_reader, request_from_reader_with_limits, request_from_stream,
+        },
+        http::request_line::Method,
+        runtime::context::RequestContext,
+        runtime::memory::MemoryBudget,
         runtime::server::Settings,
     };
 
@@ -322,6 +1146,8 @@ mod tests {
         data: &'a [u8],
         num_bytes_per_read: usize,
         pos: usize,
+        /// The buffer sizes passed to every read
+        pub requested: Vec<usize>,
     }
 
     impl<'a> ChunkReader<'a> {
@@ -330,6 +1156,7 @@ mod tests {
                 data: data.as_bytes(),
                 num_bytes_per_read: num_bytes_per_read.max(1),
                 pos: 0,
+                requested: Vec::new(),
             }
         }
     }
@@ -340,6 +1167,7 @@ mod tests {
             _cx: &mut Context<'_>,
             buf: &mut ReadBuf<'_>,
         ) -> Poll<io::Result<()>> {
+            self.requested.push(buf.remaining());
             if self.pos >= self.data.len() {
                 return Poll::Ready(Ok(()));
             }
@@ -405,7 +1233,7 @@ mod tests {
         let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
         let r = request_from_reader(&mut buffered, &settings).await.unwrap();
 
-        assert_eq!(r.request_line.method, "GET");
+        assert_eq!(r.request_line.method, Method::Get);
         assert_eq!(r.request_line.request_target, "/");
         assert_eq!(r.request_line.http_version, "1.1");
     }
@@ -426,7 +1254,7 @@ mod tests {
         let mut buffered = BufReader::new(&mut chunk_reader);
         let r = request_from_reader(&mut buffered, &settings).await.unwrap();
 
-        assert_eq!(r.request_line.method, "GET");
+        assert_eq!(r.request_line.method, Method::Get);
         assert_eq!(r.request_line.request_target, "/coffee");
         assert_eq!(r.request_line.http_version, "1.1");
     }
@@ -450,7 +1278,7 @@ mod tests {
         let mut buffered = BufReader::new(&mut chunk_reader);
         let r = request_from_reader(&mut buffered, &settings).await.unwrap();
 
-        assert_eq!(r.request_line.method, "POST");
+        assert_eq!(r.request_line.method, Method::Post);
         assert_eq!(r.request_line.request_target, "/coffee");
         assert_eq!(r.request_line.http_version, "1.1");
     }
@@ -472,7 +1300,7 @@ mod tests {
         let result = request_from_reader(&mut buffered, &settings).await;
 
         assert!(
-            matches!(result, Err(HttpError::MalformedRequestLine)),
+            matches!(result, Err(HttpError::MalformedRequestLine { .. })),
             "Expected Err(HttpError::MalformedRequestLine), got {result:?}"
         );
     }
@@ -516,7 +1344,7 @@ mod tests {
         let result = request_from_reader(&mut buffered, &settings).await;
 
         assert!(
-            matches!(result, Err(HttpError::MalformedRequestLine)),
+            matches!(result, Err(HttpError::MalformedRequestLine { .. })),
             "Expected Err(HttpError::MalformedRequestLine), got {result:?}"
         );
     }
@@ -560,7 +1388,7 @@ mod tests {
         let result = request_from_reader(&mut buffered, &settings).await;
 
         assert!(
-            matches!(result, Err(HttpError::MalformedRequestLine)),
+            matches!(result, Err(HttpError::MalformedRequestLine { .. })),
             "Expected Err(HttpError::MalformedRequestLine), got {result:?}"
         );
     }
@@ -612,7 +1440,7 @@ mod tests {
         let r = request_from_reader(&mut buffered, &settings).await;
 
         assert!(r.is_err());
-        assert!(matches!(r, Err(HttpError::MalformedHeader)));
+        assert!(matches!(r, Err(HttpError::MalformedHeader { .. })));
     }
 
     /// This test is a little contrived. It simulates the client never closing the connection through a lack of `client_write.drop()`
@@ -663,7 +1491,16 @@ mod tests {
         let r = request_from_reader(&mut buffered, &settings).await;
 
         assert!(r.is_err());
-        matches!(r, Err(HttpError::InvalidHeaders));
+        assert!(matches!(r, Err(HttpError::DuplicateCriticalHeader("host"))));
+    }
+
+    #[test]
+    fn transfer_encoding_with_content_length_should_throw_conflictingframing() {
+        let input = "POST /st HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+
+        let r = Request::from_slice(input.as_bytes());
+
+        assert!(matches!(r, Err(HttpError::ConflictingFraming)));
     }
 
     #[tokio::test]
@@ -865,4 +1702,824 @@ mod tests {
 
         assert!(matches!(r, Err(HttpError::ContentTooLarge)));
     }
+
+    #[tokio::test]
+    async fn reject_when_headers_exceeding_limit_arrive_in_one_read() {
+        let input = format!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nx-padding: {}\r\n\r\n",
+            "x".repeat(512)
+        );
+        let limits = ParseLimits {
+            max_header_size: 256,
+            ..ParseLimits::default()
+        };
+
+        let mut chunk_reader = ChunkReader::new(&input, input.len());
+        let r = request_from_reader_with_limits(&mut chunk_reader, &limits).await;
+
+        assert!(matches!(r, Err(HttpError::ContentTooLarge)));
+        assert_eq!(chunk_reader.requested.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn many_headers_read_in_small_chunks_parse_completely() {
+        let mut input = String::from("POST /st HTTP/1.1\r\nHost: localhost\r\n");
+        let padding = "x".repeat(64);
+        for i in 0..60 {
+            write!(input, "X-Header-{i}: value-{i}-{padding}\r\n").unwrap();
+        }
+        input.push_str("Content-Length: 11\r\n\r\nhello world");
+        assert!(input.len() > 4096);
+
+        let mut chunk_reader = ChunkReader::new(&input, 64);
+        let request = request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            request.headers.get("x-header-0"),
+            Some(format!("value-0-{padding}").as_str())
+        );
+        assert_eq!(
+            request.headers.get("x-header-59"),
+            Some(format!("value-59-{padding}").as_str())
+        );
+        assert_eq!(request.headers.iter().count(), 62);
+        assert_eq!(request.body, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn read_spanning_headers_and_body_is_parsed_with_any_buffer_size() {
+        let input = large_body_test_input(20_000);
+
+        for read_buffer_size in [1, 64, 8 * 1024, 64 * 1024] {
+            let limits = ParseLimits {
+                read_buffer_size,
+                ..ParseLimits::default()
+            };
+            let mut chunk_reader = ChunkReader::new(&input, input.len());
+            let request = request_from_reader_with_limits(&mut chunk_reader, &limits)
+                .await
+                .unwrap();
+
+            assert_eq!(request.headers.get("host"), Some("example.com"));
+            assert_eq!(request.body.len(), 20_000);
+            assert!(request.body.iter().all(|&byte| byte == b'x'));
+        }
+    }
+
+    #[tokio::test]
+    async fn read_buffer_grows_for_large_requests_only() {
+        let small = "GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let mut small_reader = ChunkReader::new(small, usize::MAX);
+        request_from_reader_with_limits(&mut small_reader, &ParseLimits::default())
+            .await
+            .unwrap();
+
+        assert!(
+            small_reader
+                .requested
+                .iter()
+                .all(|&size| size == INITIAL_READ_BUFFER_SIZE)
+        );
+
+        let large = large_body_test_input(100_000);
+        let mut large_reader = ChunkReader::new(&large, usize::MAX);
+        request_from_reader_with_limits(&mut large_reader, &ParseLimits::default())
+            .await
+            .unwrap();
+
+        let max_read_size = ParseLimits::default().read_buffer_size;
+        assert_eq!(large_reader.requested[0], INITIAL_READ_BUFFER_SIZE);
+        assert!(
+            large_reader
+                .requested
+                .windows(2)
+                .all(|sizes| sizes[0] <= sizes[1])
+        );
+        assert_eq!(large_reader.requested.last(), Some(&max_read_size));
+    }
+
+    #[test]
+    fn done_request_leaves_following_bytes_unparsed() {
+        let mut request = Request::initialized();
+        let data = b"POST /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\nokGET / HTTP/1.1\r\n";
+        let mut consumed = 0;
+        while request.parse_state != ParseState::Done {
+            consumed += request
+                .parse(&data[consumed..], &ParseLimits::default())
+                .unwrap();
+        }
+
+        assert_eq!(
+            request
+                .parse(&data[consumed..], &ParseLimits::default())
+                .unwrap(),
+            0
+        );
+        assert_eq!(&data[consumed..], b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn bytes_following_request_are_kept_for_next_request() {
+        let (mut server_stream, mut client) = io::duplex(4096);
+        client
+            .write_all(b"POST /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\nokGET /next HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+        let budget = MemoryBudget::new(1024 * 1024);
+        let mut pending = Vec::new();
+
+        let request = request_from_connection(
+            &mut server_stream,
+            &settings,
+            &budget,
+            &mut pending,
+            &|_| None,
+            &mut None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(request.body, b"ok");
+        assert_eq!(pending, b"GET /next HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn expect_continue_sends_interim_response_before_body() {
+        let (mut server_stream, mut client) = io::duplex(4096);
+
+        let headers = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Content-Length: 12\r\n\
+            Expect: 100-continue\r\n\
+            \r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let handle =
+            tokio::spawn(async move { request_from_stream(&mut server_stream, &settings).await });
+
+        client.write_all(headers.as_bytes()).await.unwrap();
+        let mut interim = [0u8; 25];
+        client.read_exact(&mut interim).await.unwrap();
+        assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+        client.write_all(b"hello world!").await.unwrap();
+        let r = handle.await.unwrap().unwrap();
+
+        assert_eq!(String::from_utf8(r.body).unwrap(), "hello world!");
+    }
+
+    #[tokio::test]
+    async fn chunked_body_valid() {
+        let input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            6\r\n\
+            hello \r\n\
+            6\r\n\
+            world!\r\n\
+            0\r\n\
+            \r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let mut chunk_reader = ChunkReader::new(input, 5);
+        let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
+        let r = request_from_reader(&mut buffered, &settings).await.unwrap();
+
+        assert_eq!(String::from_utf8(r.body).unwrap(), "hello world!");
+    }
+
+    /// Helper function parsing a plaintext request carrying `X-Forwarded-Proto: https`.
+    async fn forwarded_https_request(trust_forwarded_proto: bool) -> Request {
+        let input = "GET /coffee?milk=oat HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: https\r\n\r\n";
+        let mut chunk_reader = ChunkReader::new(input, 16);
+        let mut request =
+            request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default())
+                .await
+                .unwrap();
+        request.context = RequestContext {
+            is_secure: false,
+            trust_forwarded_proto,
+            ..RequestContext::default()
+        };
+        request
+    }
+
+    #[tokio::test]
+    async fn trusted_forwarded_proto_determines_scheme() {
+        let request = forwarded_https_request(true).await;
+
+        assert_eq!(request.scheme(), "https");
+        assert_eq!(
+            request.effective_uri().as_deref(),
+            Some("https://example.com/coffee?milk=oat")
+        );
+    }
+
+    #[tokio::test]
+    async fn untrusted_forwarded_proto_falls_back_to_connection() {
+        let request = forwarded_https_request(false).await;
+
+        assert_eq!(request.scheme(), "http");
+        assert_eq!(
+            request.effective_uri().as_deref(),
+            Some("http://example.com/coffee?milk=oat")
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_content_length_accepted_only_when_identical() {
+        let identical = "POST /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhello";
+        let differing = "POST /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nhello";
+
+        let mut chunk_reader = ChunkReader::new(identical, 16);
+        let request = request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default())
+            .await
+            .unwrap();
+        assert_eq!(request.headers.get("content-length"), Some("5"));
+        assert_eq!(request.body, b"hello");
+
+        let mut chunk_reader = ChunkReader::new(differing, 16);
+        let result =
+            request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default()).await;
+        assert!(matches!(result, Err(HttpError::InvalidContentLength)));
+    }
+
+    #[test]
+    fn malformed_content_length_should_throw_invalidcontentlength() {
+        for length in [
+            "+5",
+            "-5",
+            "5a",
+            "0x5",
+            "",
+            "5 5",
+            "99999999999999999999999",
+        ] {
+            let input = format!(
+                "POST /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: {length}\r\n\r\nhello"
+            );
+            let result = Request::from_slice(input.as_bytes());
+            assert!(
+                matches!(result, Err(HttpError::InvalidContentLength)),
+                "{length}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn has_body_distinguishes_empty_from_absent_bodies() {
+        let inputs = [
+            (
+                "PATCH /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n",
+                true,
+            ),
+            ("PATCH /st HTTP/1.1\r\nHost: localhost\r\n\r\n", false),
+            (
+                "PATCH /st HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+                true,
+            ),
+            (
+                "PATCH /st HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+                true,
+            ),
+        ];
+
+        for (input, has_body) in inputs {
+            let mut chunk_reader = ChunkReader::new(input, 8);
+            let request =
+                request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default())
+                    .await
+                    .unwrap();
+
+            assert_eq!(request.has_body(), has_body, "{input:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn chunked_trailers_are_merged_into_headers() {
+        let input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: Checksum\r\n\
+            \r\n\
+            6\r\n\
+            hello \r\n\
+            0\r\n\
+            Checksum: abc123\r\n\
+            Content-Length: 6\r\n\
+            \r\n";
+
+        let mut chunk_reader = ChunkReader::new(input, 7);
+        let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
+        let r = request_from_reader_with_limits(&mut buffered, &ParseLimits::default())
+            .await
+            .unwrap();
+
+        assert_eq!(r.body, b"hello ");
+        assert_eq!(r.headers.get("checksum"), Some("abc123"));
+        assert_eq!(r.headers.get("content-length"), None);
+    }
+
+    #[tokio::test]
+    async fn chunked_body_exceeding_request_size_should_throw_contenttoolarge() {
+        let mut input = String::from(
+            "POST /st HTTP/1.1\r\nHost: localhost:8080\r\nTransfer-Encoding: chunked\r\n\r\n",
+        );
+        for _ in 0..64 {
+            input.push_str("10\r\n0123456789abcdef\r\n");
+        }
+        input.push_str("0\r\n\r\n");
+        let limits = ParseLimits {
+            max_request_size: 1024,
+            ..ParseLimits::default()
+        };
+
+        let mut chunk_reader = ChunkReader::new(&input, 32);
+        let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
+        let r = request_from_reader_with_limits(&mut buffered, &limits).await;
+
+        assert!(matches!(r, Err(HttpError::ContentTooLarge)));
+    }
+
+    #[test]
+    fn signed_or_empty_chunk_size_should_throw_invalidbodylength() {
+        for size in ["+5", "-5", "", " ", "0x5", "5 5"] {
+            let input = format!(
+                "POST /st HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{size}\r\nhello\r\n0\r\n\r\n"
+            );
+
+            let r = Request::from_slice(input.as_bytes());
+
+            assert!(matches!(r, Err(HttpError::InvalidBodyLength)), "{size:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn chunk_size_exceeding_limit_should_throw_contenttoolarge() {
+        let input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            200000\r\n\
+            hello";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let mut chunk_reader = ChunkReader::new(input, 32);
+        let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
+        let r = request_from_reader(&mut buffered, &settings).await;
+
+        assert!(matches!(r, Err(HttpError::ContentTooLarge)));
+    }
+
+    #[tokio::test]
+    async fn matching_digests_verify() {
+        let input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Content-Length: 12\r\n\
+            Content-MD5: /D/5joxqDTCH1RXARz+Gdw==\r\n\
+            \r\n\
+            hello world!";
+        let sha_input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Content-Length: 12\r\n\
+            Digest: sha-256=dQnlvaDHYtK6x/kNdYtbImP6Acy8VCq1498WO+CObKk=\r\n\
+            \r\n\
+            hello world!";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let md5_request = request_from_reader(&mut input.as_bytes(), &settings)
+            .await
+            .unwrap();
+        let sha_request = request_from_reader(&mut sha_input.as_bytes(), &settings)
+            .await
+            .unwrap();
+
+        assert!(md5_request.verify_digest().unwrap());
+        assert!(sha_request.verify_digest().unwrap());
+    }
+
+    #[tokio::test]
+    async fn mismatching_digest_fails_verification() {
+        let input = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Content-Length: 12\r\n\
+            Digest: sha-256=dQnlvaDHYtK6x/kNdYtbImP6Acy8VCq1498WO+CObKk=\r\n\
+            \r\n\
+            hello world?";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let r = request_from_reader(&mut input.as_bytes(), &settings)
+            .await
+            .unwrap();
+
+        assert!(!r.verify_digest().unwrap());
+    }
+
+    #[tokio::test]
+    async fn expect_continue_without_body_should_throw_timeout() {
+        tokio::time::pause();
+        let (mut server_stream, mut client) = io::duplex(4096);
+
+        let headers = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Content-Length: 12\r\n\
+            Expect: 100-continue\r\n\
+            \r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+        let parsing_timeout = Duration::from_secs(settings.parsing_timeout);
+
+        let handle =
+            tokio::spawn(async move { request_from_stream(&mut server_stream, &settings).await });
+
+        client.write_all(headers.as_bytes()).await.unwrap();
+        let mut interim = [0u8; 25];
+        client.read_exact(&mut interim).await.unwrap();
+        let sent_continue = tokio::time::Instant::now();
+
+        let result = handle.await.unwrap();
+
+        assert!(matches!(result, Err(HttpError::Timeout)));
+        assert!(sent_continue.elapsed() >= parsing_timeout);
+    }
+
+    #[tokio::test]
+    async fn expect_continue_with_chunked_body_valid() {
+        let (mut server_stream, mut client) = io::duplex(4096);
+
+        let headers = "\
+            POST /st HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Expect: 100-continue\r\n\
+            \r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        let handle =
+            tokio::spawn(async move { request_from_stream(&mut server_stream, &settings).await });
+
+        client.write_all(headers.as_bytes()).await.unwrap();
+        let mut interim = [0u8; 25];
+        client.read_exact(&mut interim).await.unwrap();
+        assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+        client
+            .write_all(b"6\r\nhello \r\n6\r\nworld!\r\n0\r\n\r\n")
+            .await
+            .unwrap();
+        let r = handle.await.unwrap().unwrap();
+
+        assert_eq!(String::from_utf8(r.body).unwrap(), "hello world!");
+    }
+
+    #[tokio::test]
+    async fn malformed_header_reports_offset_into_request() {
+        let input = "GET / HTTP/1.1\r\nHost: localhost\r\nBad Header: x\r\n\r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.clone().try_deserialize().unwrap();
+
+        for read_size in [1, 7, input.len()] {
+            let mut chunk_reader = ChunkReader::new(input, read_size);
+            let mut buffered: BufReader<&mut ChunkReader<'_>> = BufReader::new(&mut chunk_reader);
+            let r = request_from_reader(&mut buffered, &settings).await;
+
+            assert!(
+                matches!(r, Err(HttpError::MalformedHeader { offset: 36 })),
+                "Expected Err(HttpError::MalformedHeader {{ offset: 36 }}), got {r:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn written_request_parses_to_the_same_request() {
+        let input = "POST /coffee?milk=oat HTTP/1.1\r\n\
+             Host: localhost:8080\r\n\
+             Accept: text/html\r\n\
+             Accept: */*\r\n\
+             Content-Length: 13\r\n\
+             \r\n\
+             hello, world!";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+
+        let mut chunk_reader = ChunkReader::new(input, 5);
+        let request = request_from_reader(&mut chunk_reader, &settings)
+            .await
+            .unwrap();
+
+        let mut written = Vec::new();
+        request.write(&mut written).await.unwrap();
+        let reparsed = request_from_reader(&mut written.as_slice(), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(reparsed.request_line, request.request_line);
+        assert_eq!(reparsed.headers, request.headers);
+        assert_eq!(reparsed.body, request.body);
+    }
+
+    #[tokio::test]
+    async fn written_chunked_request_parses_to_the_same_body() {
+        let input = "POST / HTTP/1.1\r\n\
+             Host: localhost:8080\r\n\
+             Transfer-Encoding: chunked\r\n\
+             \r\n\
+             5\r\nhello\r\n\
+             8\r\n, world!\r\n\
+             0\r\n\
+             \r\n";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+
+        let mut chunk_reader = ChunkReader::new(input, input.len());
+        let request = request_from_reader(&mut chunk_reader, &settings)
+            .await
+            .unwrap();
+
+        let mut written = Vec::new();
+        request.write(&mut written).await.unwrap();
+        let reparsed = request_from_reader(&mut written.as_slice(), &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(reparsed.headers, request.headers);
+        assert_eq!(reparsed.body, b"hello, world!");
+    }
+
+    #[tokio::test]
+    async fn text_limited_returns_body_under_limit() {
+        let input = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 6\r\n\r\ncafé!";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+
+        let mut chunk_reader = ChunkReader::new(input, input.len());
+        let request = request_from_reader(&mut chunk_reader, &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(request.text_limited(6).unwrap(), "café!");
+        assert!(matches!(
+            request.text_limited(5),
+            Err(HttpError::ContentTooLarge)
+        ));
+    }
+
+    #[tokio::test]
+    async fn text_limited_rejects_invalid_utf8() {
+        let input = b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n\xC3\x28";
+
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+
+        let request = request_from_reader(&mut input.as_slice(), &settings)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            request.text_limited(1024),
+            Err(HttpError::InvalidUtf8(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn trickled_headers_exceeding_header_deadline_should_throw_timeout() {
+        let config_source = File::with_name("config");
+        let settings: Settings = Config::builder()
+            .add_source(config_source)
+            .set_override("header_timeout", 10)
+            .unwrap()
+            .set_override("parsing_timeout", 30)
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let (mut client, mut server_stream) = io::duplex(1024);
+
+        let writer = tokio::spawn(async move {
+            for byte in b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n" {
+                if client.write_all(&[*byte]).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+
+        let start = tokio::time::Instant::now();
+        let result = request_from_stream(&mut server_stream, &settings).await;
+
+        assert!(matches!(result, Err(HttpError::Timeout)));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        drop(server_stream);
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn custom_limits_are_honored_without_settings() {
+        let input = large_body_test_input(2048);
+        let limits = ParseLimits {
+            max_request_size: 1024,
+            ..ParseLimits::default()
+        };
+
+        let mut chunk_reader = ChunkReader::new(&input, input.len());
+        let rejected = request_from_reader_with_limits(&mut chunk_reader, &limits).await;
+        let mut chunk_reader = ChunkReader::new(&input, input.len());
+        let accepted =
+            request_from_reader_with_limits(&mut chunk_reader, &ParseLimits::default()).await;
+
+        assert!(matches!(rejected, Err(HttpError::ContentTooLarge)));
+        assert_eq!(accepted.unwrap().body.len(), 2048);
+    }
+
+    #[tokio::test]
+    async fn is_complete_only_after_parsing_finished() {
+        let limits = ParseLimits::default();
+        let mut request = Request::initialized();
+
+        let parsed = request.parse(b"GET / HTTP/1.1\r\n", &limits).unwrap();
+        assert_eq!(parsed, 16);
+        assert!(!request.is_complete());
+
+        request.parse(b"Host: localhost\r\n\r\n", &limits).unwrap();
+        request.parse(b"", &limits).unwrap();
+        assert!(request.is_complete());
+
+        let input = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut chunk_reader = ChunkReader::new(input, 3);
+        let parsed = request_from_reader_with_limits(&mut chunk_reader, &limits)
+            .await
+            .unwrap();
+        assert!(parsed.is_complete());
+    }
+
+    #[tokio::test]
+    async fn binary_body_is_kept_byte_for_byte() {
+        let body: Vec<u8> = (0..=255u8).rev().chain([0xFF, 0x00, 0xC3, 0x28]).collect();
+        let mut input = format!(
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\nX-Raw: caf\u{e9}\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        // A latin-1 header value is not valid UTF-8 and must not change how many bytes the headers take up.
+        let latin1 = input.iter().position(|byte| *byte == 0xC3).unwrap();
+        input.splice(latin1..latin1 + 2, [0xE9]);
+        input.extend_from_slice(&body);
+
+        let request =
+            request_from_reader_with_limits(&mut input.as_slice(), &ParseLimits::default())
+                .await
+                .unwrap();
+
+        assert_eq!(request.body, body);
+    }
+
+    #[tokio::test]
+    async fn body_exceeding_spill_threshold_is_read_back_from_disk() {
+        let body: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let mut input = format!(
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        input.extend_from_slice(&body);
+        let limits = ParseLimits {
+            body_spill_threshold: Some(1024),
+            ..ParseLimits::default()
+        };
+
+        let request = request_from_reader_with_limits(&mut input.as_slice(), &limits)
+            .await
+            .unwrap();
+
+        assert!(request.is_spilled());
+        assert_eq!(request.body.len(), 1024);
+        assert_eq!(request.body_len(), body.len());
+        let mut read_back = Vec::new();
+        request
+            .body_reader()
+            .await
+            .unwrap()
+            .read_to_end(&mut read_back)
+            .await
+            .unwrap();
+        assert_eq!(read_back, body);
+    }
+
+    ///////////////////////// SYNCHRONOUS PARSING TESTS /////////////////////////////////////////
+
+    #[test]
+    fn from_slice_parses_request_line_and_headers() {
+        let request = Request::from_slice(
+            b"GET /coffee HTTP/1.1\r\nHost: localhost:8080\r\nAccept: */*\r\n\r\n",
+        )
+        .unwrap();
+
+        assert!(request.is_complete());
+        assert_eq!(request.request_line.method, Method::Get);
+        assert_eq!(request.request_line.request_target, "/coffee");
+        assert_eq!(request.headers.get("host"), Some("localhost:8080"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn from_slice_parses_body() {
+        let request = Request::from_slice(
+            b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\nContent-Length: 12\r\n\r\nhello world!",
+        )
+        .unwrap();
+
+        assert_eq!(request.body, b"hello world!");
+    }
+
+    #[test]
+    fn from_slice_parses_chunked_body() {
+        let request = Request::from_slice(
+            b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nhello \r\n6\r\nworld!\r\n0\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.body, b"hello world!");
+    }
+
+    #[test]
+    fn from_slice_incomplete_request_should_throw_unexpectedeof() {
+        let r = Request::from_slice(b"GET /coffee HTTP/1.1\r\nHost: localhost:8080\r\n");
+        assert!(matches!(r, Err(HttpError::UnexpectedEOF)));
+
+        let r = Request::from_slice(
+            b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\nContent-Length: 20\r\n\r\nhello world!",
+        );
+        assert!(matches!(r, Err(HttpError::UnexpectedEOF)));
+    }
+
+    #[test]
+    fn from_slice_body_longer_than_content_length_should_throw_invalidbodylength() {
+        let r = Request::from_slice(
+            b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\nContent-Length: 5\r\n\r\nhello world!",
+        );
+
+        assert!(matches!(r, Err(HttpError::InvalidBodyLength)));
+    }
+
+    #[test]
+    fn from_slice_without_content_length_ignores_body() {
+        let request =
+            Request::from_slice(b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\n\r\nhello world!")
+                .unwrap();
+
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn from_slice_rejects_unknown_expectation() {
+        let r = Request::from_slice(
+            b"POST /st HTTP/1.1\r\nHost: localhost:8080\r\nExpect: magic\r\nContent-Length: 2\r\n\r\nhi",
+        );
+
+        assert!(matches!(r, Err(HttpError::ExpectationFailed)));
+    }
 }
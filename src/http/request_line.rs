@@ -1,14 +1,207 @@
-use crate::http::request::HttpError;
+use std::{fmt, str::FromStr};
+
+use crate::http::{query::Query, request::HttpError};
+
+/// Enum containing the HTTP methods accepted by this application.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// Retrieves a representation of the target
+    #[default]
+    Get,
+    /// Processes the enclosed body
+    Post,
+    /// Replaces the target with the enclosed body
+    Put,
+    /// Partially modifies the target
+    Patch,
+    /// Removes the target
+    Delete,
+    /// Like GET, but without a response body
+    Head,
+    /// Describes the communication options of the target
+    Options,
+    /// Establishes a tunnel to the target
+    Connect,
+    /// Echoes the received request
+    Trace,
+}
+
+impl Method {
+    /// Returns the method name as sent on the wire.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Patch => "PATCH",
+            Self::Delete => "DELETE",
+            Self::Head => "HEAD",
+            Self::Options => "OPTIONS",
+            Self::Connect => "CONNECT",
+            Self::Trace => "TRACE",
+        }
+    }
+}
+
+/// Parses a method name, which is case-sensitive.
+impl FromStr for Method {
+    type Err = HttpError;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method {
+            "GET" => Ok(Self::Get),
+            "POST" => Ok(Self::Post),
+            "PUT" => Ok(Self::Put),
+            "PATCH" => Ok(Self::Patch),
+            "DELETE" => Ok(Self::Delete),
+            "HEAD" => Ok(Self::Head),
+            "OPTIONS" => Ok(Self::Options),
+            "CONNECT" => Ok(Self::Connect),
+            "TRACE" => Ok(Self::Trace),
+            _ => Err(HttpError::InvalidMethod(method.to_string())),
+        }
+    }
+}
+
+/// Implements Display for the Method to format it as sent on the wire.
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// A Http Request Line representation with method, target and http version
-#[derive(Debug)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct RequestLine {
     /// The method of the parsed request
-    pub method: String,
-    /// The target endpoint of the request
+    pub method: Method,
+    /// The target endpoint of the request, including the raw query string
     pub request_target: String,
     /// The HTTP version used in the request
     pub http_version: String,
+    /// The decoded query string of the request target
+    pub query: Query,
+    /// The request line as received, without the trailing CRLF
+    raw: String,
+}
+
+impl RequestLine {
+    /// Returns the request line exactly as received, without the trailing CRLF.
+    ///
+    /// Bytes that are not valid UTF-8 are replaced.
+    ///
+    /// # Examples
+    /// ```
+    /// let (request_line, _) = httpserver::http::request_line::parse_request_line("GET /a%20b?c=d&e HTTP/1.1\r\n").unwrap();
+    /// assert_eq!(request_line.unwrap().raw(), "GET /a%20b?c=d&e HTTP/1.1");
+    /// ```
+    #[must_use]
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns the method name as sent on the wire.
+    #[must_use]
+    pub const fn method_str(&self) -> &'static str {
+        self.method.as_str()
+    }
+
+    /// Returns the part of the request target before the query string.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        self.request_target
+            .split_once('?')
+            .map_or(self.request_target.as_str(), |(path, _query)| path)
+    }
+
+    /// Returns the percent-decoded path of the request target, safe to map onto a directory.
+    ///
+    /// Returns None if the path contains an invalid `%XX` sequence, decodes to invalid UTF-8 or a NUL byte, or contains a
+    /// `..` segment after decoding, which would escape the directory it is mapped onto. Unlike query strings, `+` is kept as is.
+    ///
+    /// # Examples
+    /// ```
+    /// let (request_line, _) = httpserver::http::request_line::parse_request_line("GET /foo%20bar HTTP/1.1\r\n").unwrap();
+    /// assert_eq!(request_line.unwrap().decoded_path().as_deref(), Some("/foo bar"));
+    /// ```
+    #[must_use]
+    pub fn decoded_path(&self) -> Option<String> {
+        let origin_form = self.origin_form();
+        let path = origin_form
+            .split_once('?')
+            .map_or(origin_form.as_str(), |(path, _query)| path);
+        let decoded = percent_decode_path(path)?;
+        if decoded.contains('\0') || decoded.split('/').any(|segment| segment == "..") {
+            return None;
+        }
+        Some(decoded)
+    }
+
+    /// Retrieves the decoded value of a query parameter.
+    ///
+    /// Returns None if the parameter is not part of the query string.
+    #[must_use]
+    pub fn query(&self, key: &str) -> Option<&str> {
+        self.query.get(key)
+    }
+
+    /// Returns the request target in origin-form, that being the path and query.
+    ///
+    /// Absolute-form targets have their scheme and authority stripped, an empty target is normalized to `/`. Only a target
+    /// starting with a scheme is treated as absolute-form, so a `://` in the query of an origin-form target is kept.
+    ///
+    /// # Examples
+    /// ```
+    /// let (request_line, _) =
+    ///     httpserver::http::request_line::parse_request_line("GET http://localhost:8080/coffee?milk=oat HTTP/1.1\r\n").unwrap();
+    /// assert_eq!(request_line.unwrap().origin_form(), "/coffee?milk=oat");
+    /// ```
+    #[must_use]
+    pub fn origin_form(&self) -> String {
+        let target = self.request_target.as_str();
+        let absolute = target.split_once("://").filter(|(scheme, _rest)| {
+            !scheme.is_empty()
+                && scheme
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || b"+-.".contains(&byte))
+        });
+        let target = match absolute {
+            Some((_scheme, rest)) => rest.find(['/', '?']).map_or("", |start| &rest[start..]),
+            None => target,
+        };
+
+        if target.is_empty() {
+            "/".to_string()
+        } else if target.starts_with('?') {
+            format!("/{target}")
+        } else {
+            target.to_string()
+        }
+    }
+}
+
+/// Decodes the `%XX` sequences of a path.
+///
+/// Returns None if a sequence is not followed by two hex digits or the result is not valid UTF-8.
+pub(crate) fn percent_decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
 }
 
 /// Parses a passed string into a Request Line Struct
@@ -23,7 +216,6 @@ pub struct RequestLine {
 ///
 /// This is related to the parsed data from the buffer containing RFC-incompatible formatting.
 pub fn parse_request_line(request: &str) -> Result<(Option<RequestLine>, usize), HttpError> {
-    const VALID_METHODS: &[&str] = &["GET", "POST", "PATCH", "PUT", "DELETE", "HEAD", "OPTIONS"];
     const CRLF_LEN: usize = 2;
 
     if !request.contains("\r\n") {
@@ -37,19 +229,30 @@ pub fn parse_request_line(request: &str) -> Result<(Option<RequestLine>, usize),
     // Also ensures below [i] checks cannot panic and end the application, else could also use explitic .next() and handle mnaually.
     // parts.next().ok_or(HttpError::MalformedRequestLine)?
     if parts.len() != 3 {
-        return Err(HttpError::MalformedRequestLine);
+        // Points at the end of the line if parts are missing, or at the separator of the first surplus part.
+        let offset = parts
+            .iter()
+            .take(3)
+            .map(|part| part.len() + 1)
+            .sum::<usize>()
+            - 1;
+        return Err(HttpError::MalformedRequestLine { offset });
     }
 
-    let method = parts[0].to_string();
     let request_target = parts[1].to_string();
+    let query = request_target
+        .split_once('?')
+        .map(|(_path, raw)| Query::parse(raw))
+        .unwrap_or_default();
+    let version_start = parts[0].len() + parts[1].len() + 2;
     let http_version = parts[2]
         .strip_prefix("HTTP/")
-        .ok_or(HttpError::MalformedRequestLine)?
+        .ok_or(HttpError::MalformedRequestLine {
+            offset: version_start,
+        })?
         .to_string();
 
-    if !VALID_METHODS.contains(&method.as_str()) {
-        return Err(HttpError::InvalidMethod(method));
-    }
+    let method = parts[0].parse()?;
 
     let line_length = first.len() + CRLF_LEN;
 
@@ -58,6 +261,8 @@ pub fn parse_request_line(request: &str) -> Result<(Option<RequestLine>, usize),
             method,
             request_target,
             http_version,
+            query,
+            raw: first.to_string(),
         }),
         line_length,
     ))
@@ -65,7 +270,64 @@ pub fn parse_request_line(request: &str) -> Result<(Option<RequestLine>, usize),
 
 #[cfg(test)]
 mod tests {
-    use crate::{http::request::HttpError, http::request_line::parse_request_line};
+    use crate::{
+        http::query::Query,
+        http::request::HttpError,
+        http::request_line::{Method, RequestLine, parse_request_line},
+    };
+
+    fn request_line_with_target(target: &str) -> RequestLine {
+        RequestLine {
+            method: Method::Get,
+            request_target: target.to_string(),
+            http_version: "1.1".to_string(),
+            query: Query::default(),
+            raw: String::new(),
+        }
+    }
+
+    #[test]
+    fn decoded_path_rejects_traversal_and_invalid_encodings() {
+        assert_eq!(
+            request_line_with_target("/foo%20bar?a=%2e%2e")
+                .decoded_path()
+                .as_deref(),
+            Some("/foo bar")
+        );
+        assert_eq!(
+            request_line_with_target("http://localhost/a+b/%C3%A9")
+                .decoded_path()
+                .as_deref(),
+            Some("/a+b/é")
+        );
+        for target in [
+            "/a/%2e%2e/b",
+            "/a/%2E%2e%2fetc/passwd",
+            "/../etc/passwd",
+            "/a/..",
+            "/%zz",
+            "/a%2",
+            "/%ff",
+            "/a%00.txt",
+        ] {
+            assert_eq!(
+                request_line_with_target(target).decoded_path(),
+                None,
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn raw_request_line_matches_input() {
+        let line =
+            "OPTIONS http://example.com:8080/a/b%2Fc;v=1?q=caf%C3%A9+au+lait&flag&x=%zz HTTP/1.1";
+        let (result, _) = parse_request_line(&format!("{line}\r\nHost: example.com\r\n")).unwrap();
+
+        let request_line = result.unwrap();
+        assert_eq!(request_line.raw(), line);
+        assert_eq!(request_line.method, Method::Options);
+    }
 
     #[test]
     fn get_request_line_valid() {
@@ -78,7 +340,7 @@ mod tests {
 
         assert!(result.is_some());
         let request_line = result.unwrap();
-        assert_eq!(request_line.method, "GET");
+        assert_eq!(request_line.method, Method::Get);
         assert_eq!(request_line.request_target, "/");
         assert_eq!(request_line.http_version, "1.1");
         assert_eq!(result_size, 16);
@@ -96,7 +358,7 @@ mod tests {
 
         assert!(result.is_some());
         let request_line = result.unwrap();
-        assert_eq!(request_line.method, "GET");
+        assert_eq!(request_line.method, Method::Get);
         assert_eq!(request_line.request_target, "/coffee");
         assert_eq!(request_line.http_version, "1.1");
         assert_eq!(result_size, 22);
@@ -118,7 +380,7 @@ mod tests {
         let result = parse_request_line(input);
         assert!(result.is_err());
         assert!(
-            matches!(result, Err(HttpError::MalformedRequestLine)),
+            matches!(result, Err(HttpError::MalformedRequestLine { offset: 18 })),
             "Expected Err(HttpError::MalformedRequestLine), got {result:?}"
         );
     }
@@ -130,7 +392,7 @@ mod tests {
         let result = parse_request_line(input);
         assert!(result.is_err());
         assert!(
-            matches!(result, Err(HttpError::MalformedRequestLine)),
+            matches!(result, Err(HttpError::MalformedRequestLine { offset: 12 })),
             "Expected Err(HttpError::MalformedRequestLine), got {result:?}"
         );
     }
@@ -146,4 +408,74 @@ mod tests {
             "Expected Err(HttpError::InvalidMethod), got {result:?}"
         );
     }
+
+    #[test]
+    fn methods_roundtrip_through_display() {
+        for method in [
+            Method::Get,
+            Method::Post,
+            Method::Put,
+            Method::Patch,
+            Method::Delete,
+            Method::Head,
+            Method::Options,
+            Method::Connect,
+            Method::Trace,
+        ] {
+            assert_eq!(method.to_string().parse::<Method>().unwrap(), method);
+        }
+        assert!(matches!(
+            "get".parse::<Method>(),
+            Err(HttpError::InvalidMethod(_))
+        ));
+    }
+
+    #[test]
+    fn origin_form_target_is_unchanged() {
+        let request_line = request_line_with_target("/coffee?milk=oat");
+
+        assert_eq!(request_line.origin_form(), "/coffee?milk=oat");
+    }
+
+    #[test]
+    fn origin_form_target_with_url_in_query_is_unchanged() {
+        let request_line = request_line_with_target("/login?next=https://evil.com/x");
+
+        assert_eq!(request_line.origin_form(), "/login?next=https://evil.com/x");
+        assert_eq!(request_line.decoded_path().as_deref(), Some("/login"));
+    }
+
+    #[test]
+    fn absolute_form_target_is_stripped_to_path_and_query() {
+        let with_path = request_line_with_target("http://localhost:8080/coffee?milk=oat");
+        let without_path = request_line_with_target("https://localhost:8080");
+
+        assert_eq!(with_path.origin_form(), "/coffee?milk=oat");
+        assert_eq!(without_path.origin_form(), "/");
+    }
+
+    #[test]
+    fn empty_target_is_normalized_to_root() {
+        let request_line = request_line_with_target("");
+
+        assert_eq!(request_line.origin_form(), "/");
+    }
+
+    #[test]
+    fn query_is_parsed_and_target_kept_intact() {
+        let input = "GET /search?q=rust+http&page=2&flag HTTP/1.1\r\n";
+
+        let (result, _) = parse_request_line(input).unwrap();
+        let request_line = result.unwrap();
+
+        assert_eq!(
+            request_line.request_target,
+            "/search?q=rust+http&page=2&flag"
+        );
+        assert_eq!(request_line.path(), "/search");
+        assert_eq!(request_line.query("q"), Some("rust http"));
+        assert_eq!(request_line.query("page"), Some("2"));
+        assert_eq!(request_line.query("flag"), Some(""));
+        assert_eq!(request_line.query("missing"), None);
+    }
 }
@@ -1,14 +1,28 @@
 use std::{
-    fmt::{self},
-    io::{self},
+    fmt::{self, Write as _},
+    io::{self, Write as _},
+    path::Path,
+    time::{Duration, SystemTime},
 };
 
+use flate2::{
+    Compression,
+    write::{GzEncoder, ZlibEncoder},
+};
+
+use bytes::Bytes;
 use tokio::{
-    fs::read_to_string,
-    io::{AsyncWrite, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{Receiver, Sender, channel},
 };
 
-use crate::{http::headers::Headers, http::request::HttpError};
+use crate::{
+    http::date::http_date,
+    http::fs,
+    http::headers::Headers,
+    http::limits::ParseLimits,
+    http::request::{HttpError, Request, find_crlf, parse_chunk_size},
+};
 
 /// Representation of a HTTP response with status code, headers and body
 #[derive(Debug)]
@@ -19,25 +33,374 @@ pub struct Response {
     pub headers: Headers,
     /// A byte vector representing the body
     pub body: Vec<u8>,
+    /// An optional channel the body is streamed from instead of `body`
+    pub channel: Option<Receiver<Bytes>>,
+}
+
+impl Response {
+    /// Creates a response whose body is streamed from the passed channel.
+    ///
+    /// The server writes every received buffer until all senders are dropped.
+    /// Without a `content-length` header the buffers are sent as chunks using `Transfer-Encoding: chunked`, otherwise they are written as is.
+    #[must_use]
+    pub const fn from_channel(
+        status: StatusCode,
+        headers: Headers,
+        channel: Receiver<Bytes>,
+    ) -> Self {
+        Self {
+            status,
+            headers,
+            body: Vec::new(),
+            channel: Some(channel),
+        }
+    }
+
+    /// Creates a response with a body of unknown length, which the handler writes incrementally through the returned writer.
+    ///
+    /// The body is sent using `Transfer-Encoding: chunked`, any `content-length` in the passed headers is removed. The response
+    /// is complete once the writer and all of its clones are dropped, so the writer is usually moved into a spawned task.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::{
+    ///     http::{headers::Headers, response::{Response, StatusCode}},
+    ///     runtime::router::Router,
+    /// };
+    /// let mut router = Router::new();
+    /// router.route("/lines", |_req| async {
+    ///     let (response, writer) = Response::chunked(StatusCode::Ok, Headers::new());
+    ///     tokio::spawn(async move {
+    ///         for line in ["first\n", "second\n"] {
+    ///             if writer.write(line).await.is_err() {
+    ///                 break;
+    ///             }
+    ///         }
+    ///     });
+    ///     response
+    /// });
+    /// ```
+    #[must_use]
+    pub fn chunked(status: StatusCode, mut headers: Headers) -> (Self, ChunkedResponseWriter) {
+        const BUFFERED_CHUNKS: usize = 16;
+        headers.remove("content-length");
+        let (sender, receiver) = channel(BUFFERED_CHUNKS);
+        (
+            Self::from_channel(status, headers, receiver),
+            ChunkedResponseWriter { sender },
+        )
+    }
+
+    /// Returns a builder for a `200 OK` response without headers or body.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::http::response::{Response, StatusCode};
+    /// let response = Response::builder()
+    ///     .status(StatusCode::Created)
+    ///     .header("Content-Type", "text/plain")
+    ///     .body("created")
+    ///     .build();
+    /// assert_eq!(response.headers.get("content-length"), Some("7"));
+    /// ```
+    #[must_use]
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::default()
+    }
+
+    /// Creates a `200 OK` response the client saves as a file with the passed name instead of displaying it.
+    ///
+    /// Quotes and backslashes in the filename are escaped. A filename containing non-ASCII characters is additionally sent
+    /// percent-encoded as `filename*=UTF-8''...` following RFC 5987, with those characters replaced by `_` in the plain `filename`.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::http::response::Response;
+    /// let response = Response::download("a,b", "report.csv", "text/csv");
+    /// assert_eq!(response.headers.get("content-disposition"), Some("attachment; filename=\"report.csv\""));
+    /// ```
+    #[must_use]
+    pub fn download(body: impl Into<Vec<u8>>, filename: &str, content_type: &str) -> Self {
+        Self::builder()
+            .header("content-type", content_type)
+            .header("content-disposition", content_disposition(filename))
+            .body(body)
+            .build()
+    }
+
+    /// Creates a `503 Service Unavailable` response, such as for maintenance windows or overload.
+    ///
+    /// A passed `retry_after` is sent as `Retry-After` header, see `RetryAfter`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use httpserver::http::response::Response;
+    /// let response = Response::service_unavailable(Some(Duration::from_secs(120).into()));
+    /// assert_eq!(response.headers.get("retry-after"), Some("120"));
+    /// ```
+    #[must_use]
+    pub fn service_unavailable(retry_after: Option<RetryAfter>) -> Self {
+        let html = "<html><body><h1>Service Unavailable</h1></body></html>";
+        with_retry_after(
+            html_response(StatusCode::ServiceUnavailable, html),
+            retry_after,
+        )
+    }
+
+    /// Creates a `429 Too Many Requests` response for clients exceeding a rate limit.
+    ///
+    /// A passed `retry_after` is sent as `Retry-After` header, see `RetryAfter`.
+    #[must_use]
+    pub fn too_many_requests(retry_after: Option<RetryAfter>) -> Self {
+        let html = "<html><body><h1>Too Many Requests</h1></body></html>";
+        with_retry_after(
+            html_response(StatusCode::TooManyRequests, html),
+            retry_after,
+        )
+    }
+
+    /// Whether the body is text-like according to its `content-type` header, see `Headers::is_text_content`.
+    #[must_use]
+    pub fn is_text_content(&self) -> bool {
+        self.headers.is_text_content()
+    }
+
+    /// Whether compressing the body is worthwhile.
+    ///
+    /// Bodies smaller than `min_size` bytes, already encoded bodies, streamed bodies and already compressed media types
+    /// such as images, video or archives are left as is, see `Headers::is_compressed_content`.
+    ///
+    /// Partial content is left as is as well, as its `Content-Range` refers to the bytes of the uncompressed representation.
+    #[must_use]
+    pub fn is_compressible(&self, min_size: usize) -> bool {
+        self.channel.is_none()
+            && self.status != StatusCode::PartialContent
+            && !self.headers.contains_key("content-range")
+            && self.body.len() >= min_size
+            && !self.headers.contains_key("content-encoding")
+            && !self.headers.is_compressed_content()
+    }
+}
+
+/// Writes the body of a response created with `Response::chunked`, sending every write as a separate chunk.
+#[derive(Clone, Debug)]
+pub struct ChunkedResponseWriter {
+    /// The sending half of the channel the response body is streamed from
+    sender: Sender<Bytes>,
+}
+
+impl ChunkedResponseWriter {
+    /// Writes the passed data as the next chunk of the body, waiting while the previous chunks are still being sent.
+    ///
+    /// Empty data is skipped, as an empty chunk would terminate the body.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` with `BrokenPipe` if the response is no longer being written, such as after the client disconnected.
+    pub async fn write(&self, data: impl Into<Bytes>) -> Result<(), HttpError> {
+        let data = data.into();
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .send(data)
+            .await
+            .map_err(|_| HttpError::Io(io::ErrorKind::BrokenPipe.into()))
+    }
+}
+
+/// Builds the `Content-Disposition` value for an attachment with the passed filename, see `Response::download`.
+fn content_disposition(filename: &str) -> String {
+    let mut fallback = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        match c {
+            '"' | '\\' => {
+                fallback.push('\\');
+                fallback.push(c);
+            }
+            ' '..='~' => fallback.push(c),
+            _ => fallback.push('_'),
+        }
+    }
+
+    if filename.is_ascii() && !filename.chars().any(|c| c.is_ascii_control()) {
+        return format!("attachment; filename=\"{fallback}\"");
+    }
+
+    let mut encoded = String::with_capacity(filename.len() * 3);
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            // Writing to a String cannot fail.
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+/// Builder for a `Response`, created through `Response::builder`.
+#[derive(Debug, Default)]
+pub struct ResponseBuilder {
+    /// The status code of the built response
+    status: StatusCode,
+    /// The headers of the built response
+    headers: Headers,
+    /// The body of the built response
+    body: Vec<u8>,
+}
+
+impl ResponseBuilder {
+    /// Sets the status code, `200 OK` if not called.
+    #[must_use]
+    pub const fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Adds a header, storing the key lowercase and appending repeated keys.
+    #[must_use]
+    pub fn header(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.headers.append(key.to_lowercase(), value);
+        self
+    }
+
+    /// Sets the body, replacing any previously set one.
+    #[must_use]
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Builds the response, setting `content-length` to the body length unless a header set it explicitly.
+    #[must_use]
+    pub fn build(mut self) -> Response {
+        if self.headers.get("content-length").is_none() {
+            self.headers
+                .insert("content-length", self.body.len().to_string());
+        }
+        Response {
+            status: self.status,
+            headers: self.headers,
+            body: self.body,
+            channel: None,
+        }
+    }
+}
+
+/// The value of a `Retry-After` header, telling the client when to repeat a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAfter {
+    /// Retry after the delay, sent in whole seconds rounded up so clients never retry too early
+    Delay(Duration),
+    /// Retry at the point in time, sent as IMF-fixdate
+    At(SystemTime),
+}
+
+impl RetryAfter {
+    /// Formats the header value.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use httpserver::http::response::RetryAfter;
+    /// assert_eq!(RetryAfter::Delay(Duration::from_millis(1500)).header_value(), "2");
+    /// assert_eq!(RetryAfter::At(UNIX_EPOCH).header_value(), "Thu, 01 Jan 1970 00:00:00 GMT");
+    /// ```
+    #[must_use]
+    pub fn header_value(&self) -> String {
+        match self {
+            Self::Delay(delay) => {
+                (delay.as_secs() + u64::from(delay.subsec_nanos() > 0)).to_string()
+            }
+            Self::At(time) => http_date(*time),
+        }
+    }
+}
+
+impl From<Duration> for RetryAfter {
+    fn from(delay: Duration) -> Self {
+        Self::Delay(delay)
+    }
+}
+
+impl From<SystemTime> for RetryAfter {
+    fn from(time: SystemTime) -> Self {
+        Self::At(time)
+    }
+}
+
+/// Sets the `Retry-After` header of the passed response if a value is given.
+fn with_retry_after(mut response: Response, retry_after: Option<RetryAfter>) -> Response {
+    if let Some(retry_after) = retry_after {
+        response
+            .headers
+            .insert("retry-after", retry_after.header_value());
+    }
+    response
 }
 
 /// Enum containing the valid status codes used in this application.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum StatusCode {
+    /// Represents the interim response allowing the client to send the body
+    Continue = 100,
+    /// Represents the server switching to the protocol requested in the `Upgrade` header
+    SwitchingProtocols = 101,
     /// Represents a successful response
+    #[default]
     Ok = 200,
     /// Represents a successful creation
     Created = 201,
+    /// Represents a successful response without a body
+    NoContent = 204,
+    /// Represents a response containing only the requested ranges of the representation
+    PartialContent = 206,
     /// Represents a redirect
     MovedPermanently = 301,
+    /// Represents a temporary redirect
+    Found = 302,
+    /// Represents a redirect to be followed with a GET request
+    SeeOther = 303,
+    /// Represents the cached representation still being valid
+    NotModified = 304,
+    /// Represents a temporary redirect that must be repeated with the same method and body
+    TemporaryRedirect = 307,
+    /// Represents a redirect that must be repeated with the same method and body
+    PermanentRedirect = 308,
     /// Represents an invalid request
     BadRequest = 400,
+    /// Represents the request lacking valid authentication
+    Unauthorized = 401,
+    /// Represents the client not being allowed to access the target
+    Forbidden = 403,
     /// Represents the request target not being found as a valid endpoint
     NotFound = 404,
+    /// Represents the target not supporting the request method
+    MethodNotAllowed = 405,
     /// Represents the client taking too long to send the complete request.
     RequestTimeout = 408,
+    /// Represents the request conflicting with the current state of the target
+    Conflict = 409,
+    /// Represents the request being larger than the server is willing to process.
+    ContentTooLarge = 413,
+    /// Represents the body using a content type the target does not accept
+    UnsupportedMediaType = 415,
+    /// Represents none of the requested ranges overlapping the representation
+    RangeNotSatisfiable = 416,
+    /// Represents an `Expect` header that cannot be met by the server.
+    ExpectationFailed = 417,
+    /// Represents the client sending too many requests in a given time
+    TooManyRequests = 429,
     /// Represents an internal error of the server
     InternalServerError = 500,
+    /// Represents the server not supporting the functionality required by the request
+    NotImplemented = 501,
+    /// Represents an invalid response from an upstream server
+    BadGateway = 502,
+    /// Represents the server being temporarily unable to handle the request
+    ServiceUnavailable = 503,
     /// Represents the server taking too long to respond to the request
     GatewayTimeout = 504,
 }
@@ -50,22 +413,458 @@ impl fmt::Display for StatusCode {
 }
 
 impl StatusCode {
+    /// Returns the status code matching the passed numeric value.
+    ///
+    /// Returns None if the code is not used in this application.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::http::response::StatusCode;
+    /// assert_eq!(StatusCode::from_u16(404), Some(StatusCode::NotFound));
+    /// assert_eq!(StatusCode::from_u16(999), None);
+    /// ```
+    #[must_use]
+    pub const fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            100 => Some(Self::Continue),
+            101 => Some(Self::SwitchingProtocols),
+            200 => Some(Self::Ok),
+            201 => Some(Self::Created),
+            204 => Some(Self::NoContent),
+            206 => Some(Self::PartialContent),
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            303 => Some(Self::SeeOther),
+            304 => Some(Self::NotModified),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
+            400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            403 => Some(Self::Forbidden),
+            404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            408 => Some(Self::RequestTimeout),
+            409 => Some(Self::Conflict),
+            413 => Some(Self::ContentTooLarge),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            417 => Some(Self::ExpectationFailed),
+            429 => Some(Self::TooManyRequests),
+            500 => Some(Self::InternalServerError),
+            501 => Some(Self::NotImplemented),
+            502 => Some(Self::BadGateway),
+            503 => Some(Self::ServiceUnavailable),
+            504 => Some(Self::GatewayTimeout),
+            _ => None,
+        }
+    }
+
+    /// Whether the status code is informational (1xx).
+    #[must_use]
+    pub const fn is_informational(&self) -> bool {
+        matches!(*self as u16, 100..=199)
+    }
+
+    /// Whether the status code indicates success (2xx).
+    #[must_use]
+    pub const fn is_success(&self) -> bool {
+        matches!(*self as u16, 200..=299)
+    }
+
+    /// Whether the status code is a redirection (3xx).
+    #[must_use]
+    pub const fn is_redirection(&self) -> bool {
+        matches!(*self as u16, 300..=399)
+    }
+
+    /// Whether a client following this redirect has to repeat the request with the same method and body.
+    ///
+    /// This is only guaranteed for `307 Temporary Redirect` and `308 Permanent Redirect`. Clients commonly turn a `POST` into a
+    /// `GET` when following `301` or `302`, and `303 See Other` always has to be followed with a `GET`.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::http::response::StatusCode;
+    /// assert!(StatusCode::PermanentRedirect.preserves_method());
+    /// assert!(!StatusCode::MovedPermanently.preserves_method());
+    /// ```
+    #[must_use]
+    pub const fn preserves_method(&self) -> bool {
+        matches!(self, Self::TemporaryRedirect | Self::PermanentRedirect)
+    }
+
+    /// Whether the status code indicates a client error (4xx).
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::http::response::StatusCode;
+    /// assert!(StatusCode::NotFound.is_client_error());
+    /// assert!(!StatusCode::InternalServerError.is_client_error());
+    /// ```
+    #[must_use]
+    pub const fn is_client_error(&self) -> bool {
+        matches!(*self as u16, 400..=499)
+    }
+
+    /// Whether the status code indicates a server error (5xx).
+    #[must_use]
+    pub const fn is_server_error(&self) -> bool {
+        matches!(*self as u16, 500..=599)
+    }
+
     /// Creates the string representation of the passed status code.
     #[must_use]
     const fn reason_phrase(&self) -> &str {
         match self {
+            Self::Continue => "Continue",
+            Self::SwitchingProtocols => "Switching Protocols",
             Self::Ok => "OK",
             Self::Created => "Created",
+            Self::NoContent => "No Content",
+            Self::PartialContent => "Partial Content",
             Self::MovedPermanently => "Moved Permanently",
+            Self::Found => "Found",
+            Self::SeeOther => "See Other",
+            Self::NotModified => "Not Modified",
+            Self::TemporaryRedirect => "Temporary Redirect",
+            Self::PermanentRedirect => "Permanent Redirect",
             Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
             Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
             Self::RequestTimeout => "Request Timeout",
+            Self::Conflict => "Conflict",
+            Self::ContentTooLarge => "Content Too Large",
+            Self::UnsupportedMediaType => "Unsupported Media Type",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::ExpectationFailed => "Expectation Failed",
+            Self::TooManyRequests => "Too Many Requests",
             Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
             Self::GatewayTimeout => "Gateway Timeout",
         }
     }
 }
 
+/// Parses the contents of a reader to a Response
+///
+/// Mirrors `request_from_reader`: reads the status line and headers, followed by a body framed by `Content-Length` or `Transfer-Encoding: chunked`.
+/// Without either the body is read until the reader is exhausted. Trailers following a chunked body are discarded.
+/// Informational (1xx), `204 No Content` and `304 Not Modified` responses never have a body.
+///
+/// # Errors
+///
+/// Throws `HttpError::MalformedStatusLine` if the status line is invalid or contains an unknown status code.
+///
+/// Throws `HttpError::UnexpectedEOF` if the reader ends before the response is complete. This includes a chunked body
+/// ending before its terminating `0\r\n\r\n`, so a proxy never mistakes a dropped upstream connection for a complete body.
+///
+/// Throws `HttpError::ContentTooLarge` if the status line and headers exceed the default `max_header_size`, the body
+/// exceeds the default `max_request_size` or a chunk exceeds the default `max_chunk_size`.
+pub async fn response_from_reader<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Response, HttpError> {
+    response_from_reader_with_limits(reader, &ParseLimits::default()).await
+}
+
+/// Parses the contents of a reader to a Response, limiting the head to `max_header_size`, the body to `max_request_size`
+/// and chunks to `max_chunk_size` of the passed limits.
+///
+/// # Errors
+///
+/// Throws a `HttpError` if the response was not valid or a chunk exceeds the limit, see `response_from_reader`.
+pub async fn response_from_reader_with_limits<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    limits: &ParseLimits,
+) -> Result<Response, HttpError> {
+    const CRLF_LEN: usize = 2;
+    let mut buffer = Vec::new();
+
+    let mut search_start = 0;
+    let header_end = loop {
+        if let Some(position) = buffer[search_start..]
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+        {
+            break search_start + position + 2 * CRLF_LEN;
+        }
+        if buffer.len() > limits.max_header_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+        // The terminator may straddle two reads, so the last 3 bytes are searched again.
+        search_start = buffer.len().saturating_sub(3);
+        read_more(reader, &mut buffer).await?;
+    };
+    if header_end > limits.max_header_size {
+        return Err(HttpError::ContentTooLarge);
+    }
+
+    let status_line_end = find_crlf(&buffer).ok_or(HttpError::InternalInvariantViolated)?;
+    let status = parse_status_line(&String::from_utf8_lossy(&buffer[..status_line_end]))?;
+
+    let headers_start = status_line_end + CRLF_LEN;
+    let mut headers = Headers::new();
+    headers
+        .parse_header(&buffer[headers_start..header_end])
+        .map_err(|e| e.shifted(headers_start))?;
+
+    let mut body = buffer.split_off(header_end);
+    let chunked = headers
+        .get("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+
+    if status.is_informational()
+        || status == StatusCode::NoContent
+        || status == StatusCode::NotModified
+    {
+        body.clear();
+    } else if chunked {
+        body = read_chunked_body(reader, body, limits).await?;
+    } else if let Some(content) = headers.get("content-length") {
+        let content_length: usize = content.parse()?;
+        if content_length > limits.max_request_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+        while body.len() < content_length {
+            read_more(reader, &mut body).await?;
+        }
+        if body.len() > content_length {
+            return Err(HttpError::InvalidBodyLength);
+        }
+    } else {
+        let remaining = (limits.max_request_size + 1).saturating_sub(body.len());
+        reader.take(remaining as u64).read_to_end(&mut body).await?;
+        if body.len() > limits.max_request_size {
+            return Err(HttpError::ContentTooLarge);
+        }
+    }
+
+    Ok(Response {
+        status,
+        headers,
+        body,
+        channel: None,
+    })
+}
+
+/// Parses a status line such as `HTTP/1.1 200 OK`, ignoring the reason phrase.
+fn parse_status_line(line: &str) -> Result<StatusCode, HttpError> {
+    let malformed = || HttpError::MalformedStatusLine(line.to_string());
+    let (version, rest) = line.split_once(' ').ok_or_else(malformed)?;
+    if !version.starts_with("HTTP/") {
+        return Err(malformed());
+    }
+
+    let code = rest.split(' ').next().unwrap_or_default();
+    code.parse()
+        .ok()
+        .and_then(StatusCode::from_u16)
+        .ok_or_else(malformed)
+}
+
+/// Decodes a chunked body, starting with the already buffered data and reading the rest from the reader.
+async fn read_chunked_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    mut data: Vec<u8>,
+    limits: &ParseLimits,
+) -> Result<Vec<u8>, HttpError> {
+    const CRLF_LEN: usize = 2;
+    let mut body = Vec::new();
+
+    loop {
+        let Some(line_end) = find_crlf(&data) else {
+            read_more(reader, &mut data).await?;
+            continue;
+        };
+
+        let chunk_size = parse_chunk_size(&String::from_utf8_lossy(&data[..line_end]))?;
+        if chunk_size > limits.max_chunk_size
+            || body.len().saturating_add(chunk_size) > limits.max_request_size
+        {
+            return Err(HttpError::ContentTooLarge);
+        }
+        let chunk_start = line_end + CRLF_LEN;
+
+        if chunk_size == 0 {
+            data.drain(..chunk_start);
+            loop {
+                match find_crlf(&data) {
+                    Some(0) => return Ok(body),
+                    Some(trailer_end) => {
+                        data.drain(..trailer_end + CRLF_LEN);
+                    }
+                    None => read_more(reader, &mut data).await?,
+                }
+            }
+        }
+
+        let chunk_end = chunk_start
+            .checked_add(chunk_size)
+            .ok_or(HttpError::InvalidBodyLength)?;
+        while data.len() < chunk_end + CRLF_LEN {
+            read_more(reader, &mut data).await?;
+        }
+
+        if &data[chunk_end..chunk_end + CRLF_LEN] != b"\r\n" {
+            return Err(HttpError::InvalidBodyLength);
+        }
+
+        body.extend_from_slice(&data[chunk_start..chunk_end]);
+        data.drain(..chunk_end + CRLF_LEN);
+    }
+}
+
+/// Reads the next batch of bytes from the reader into the buffer.
+///
+/// # Errors
+///
+/// Throws `HttpError::UnexpectedEOF` if the reader is exhausted.
+async fn read_more<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+) -> Result<(), HttpError> {
+    let mut temp = [0u8; 64];
+    let read = reader.read(&mut temp).await?;
+    if read == 0 {
+        return Err(HttpError::UnexpectedEOF);
+    }
+    buffer.extend_from_slice(&temp[..read]);
+    Ok(())
+}
+
+/// Writes the entire response to the passed writer and flushes it.
+///
+/// Writes the status line, the headers and the body in that order. A body streamed from a channel is sent as chunks
+/// using `Transfer-Encoding: chunked` unless the response declares a `content-length`.
+///
+/// # Errors
+///
+/// This function will return an `HttpError::UnsupportedTransferEncoding` without writing anything if the response declares
+/// a transfer-encoding other than `chunked` as its final encoding, and an `HttpError::Io` if any write operation to the
+/// underlying writer fails.
+pub async fn write_response<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    response: Response,
+) -> Result<(), HttpError> {
+    write_response_without_flush(&mut writer, response).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes the response like `write_response`, leaving the flush to the caller.
+///
+/// Bodies streamed from a channel are still flushed after every buffer, so they reach the client as they are produced.
+/// A response declaring `Transfer-Encoding: chunked` itself has its body sent as chunks and any `content-length` removed.
+///
+/// # Errors
+///
+/// This function will return an `HttpError::UnsupportedTransferEncoding` before writing anything if the response declares
+/// a transfer-encoding other than `chunked` as its final encoding, and an `HttpError::Io` if any write operation to the
+/// underlying writer fails.
+pub(crate) async fn write_response_without_flush<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    response: Response,
+) -> Result<(), HttpError> {
+    let Response {
+        status,
+        mut headers,
+        body,
+        channel,
+    } = response;
+    let declared_chunked = declares_chunked(&headers)?;
+    if declared_chunked {
+        headers.remove("content-length");
+    }
+    write_status_line(&mut writer, status).await?;
+
+    if let Some(mut channel) = channel {
+        let chunked = declared_chunked || headers.get("content-length").is_none();
+        if chunked && !declared_chunked {
+            headers.insert("transfer-encoding", "chunked");
+        }
+        write_headers(&mut writer, &mut headers).await?;
+        while let Some(data) = channel.recv().await {
+            if !chunked {
+                writer.write_all(&data).await?;
+            } else if !data.is_empty() {
+                write_chunked_body(&mut writer, &data).await?;
+            }
+            writer.flush().await?;
+        }
+        if chunked {
+            write_final_body_chunk(&mut writer, None).await?;
+        }
+    } else if declared_chunked {
+        write_headers(&mut writer, &mut headers).await?;
+        if !body.is_empty() {
+            write_chunked_body(&mut writer, &body).await?;
+        }
+        write_final_body_chunk(&mut writer, None).await?;
+    } else {
+        write_headers(&mut writer, &mut headers).await?;
+        writer.write_all(&body).await?;
+    }
+    Ok(())
+}
+
+/// Whether the response headers declare `Transfer-Encoding: chunked`.
+///
+/// `chunked` is the only transfer-encoding the server frames bodies with, so it has to be the final encoding and may only
+/// appear once. Other encodings such as `gzip` may precede it.
+///
+/// # Errors
+///
+/// Throws an `HttpError::UnsupportedTransferEncoding` for any other transfer-encoding.
+fn declares_chunked(headers: &Headers) -> Result<bool, HttpError> {
+    let codings = headers.get_list("transfer-encoding");
+    let Some((last, preceding)) = codings.split_last() else {
+        return Ok(false);
+    };
+    if !last.eq_ignore_ascii_case("chunked")
+        || preceding
+            .iter()
+            .any(|coding| coding.eq_ignore_ascii_case("chunked"))
+    {
+        return Err(HttpError::UnsupportedTransferEncoding(codings.join(", ")));
+    }
+    Ok(true)
+}
+
+/// Writes the status line and headers of the response as an answer to a `HEAD` request, leaving the flush to the caller.
+///
+/// The body is dropped, while the headers stay the same as for a `GET`. A body streamed from a channel without a
+/// `content-length` is therefore still announced with `Transfer-Encoding: chunked`.
+///
+/// # Errors
+///
+/// This function will return an `HttpError::Io` if any write operation to the underlying writer fails.
+pub(crate) async fn write_head_response_without_flush<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    response: Response,
+) -> Result<(), HttpError> {
+    let Response {
+        status,
+        mut headers,
+        channel,
+        ..
+    } = response;
+    if declares_chunked(&headers)? {
+        headers.remove("content-length");
+    }
+    write_status_line(&mut writer, status).await?;
+    if channel.is_some()
+        && headers.get("transfer-encoding").is_none()
+        && headers.get("content-length").is_none()
+    {
+        headers.insert("transfer-encoding", "chunked");
+    }
+    write_headers(&mut writer, &mut headers).await?;
+    Ok(())
+}
+
 /// Write the status line to the passed writer.
 ///
 /// Hardcodes HTTP/1.1 due to the limit of the Server to that version.
@@ -88,7 +887,8 @@ pub async fn write_status_line<W: AsyncWrite + Unpin>(
 
 /// Writes the headers to the passed writer.
 ///
-/// Given a hashmap of headers, iterates through them and prints the keys and values in HTTP valid format.
+/// Given the headers, iterates through them in insertion order and prints the keys and values in HTTP valid format.
+/// A header with several values, such as `Set-Cookie`, is written as one line per value.
 /// Also prints the final linebreak separating headers from the HTTP body.
 ///
 /// # Errors
@@ -98,7 +898,7 @@ pub async fn write_headers<W: AsyncWrite + Unpin>(
     mut writer: W,
     headers: &mut Headers,
 ) -> io::Result<()> {
-    for (key, value) in headers.iter() {
+    for (key, value) in headers.iter_all() {
         let line = format!("{key}: {value}\r\n");
         writer.write_all(line.as_bytes()).await?;
     }
@@ -165,7 +965,7 @@ pub async fn write_trailers<W: AsyncWrite + Unpin>(
     mut writer: W,
     headers: &Headers,
 ) -> Result<(), HttpError> {
-    for (key, value) in headers.iter() {
+    for (key, value) in headers.iter_all() {
         writer
             .write_all(format!("{}: {}\r\n", key.to_lowercase(), value).as_bytes())
             .await?;
@@ -174,6 +974,26 @@ pub async fn write_trailers<W: AsyncWrite + Unpin>(
     Ok(())
 }
 
+/// Returns the headers framing a response body, to be merged into the headers of a manually written response.
+///
+/// A known body length results in `content-length`, an unknown one in `transfer-encoding: chunked`, in which case the body
+/// has to be written using `write_chunked_body` and `write_final_body_chunk`.
+///
+/// # Examples
+/// ```
+/// let headers = httpserver::http::response::response_framing_headers(None);
+/// assert_eq!(headers.get("transfer-encoding"), Some("chunked"));
+/// ```
+#[must_use]
+pub fn response_framing_headers(body_len: Option<u64>) -> Headers {
+    let mut headers = Headers::new();
+    match body_len {
+        Some(len) => headers.insert("content-length", len.to_string()),
+        None => headers.insert("transfer-encoding", "chunked"),
+    }
+    headers
+}
+
 /// Helper function to remove boilerplate for creating html responses with associated headers.
 #[must_use]
 pub fn html_response(status: StatusCode, html: &str) -> Response {
@@ -184,33 +1004,171 @@ pub fn html_response(status: StatusCode, html: &str) -> Response {
         status,
         headers,
         body: html.as_bytes().to_vec(),
+        channel: None,
+    }
+}
+
+/// Helper function to remove boilerplate for creating JSON responses from an already serialized body.
+#[must_use]
+pub fn json_response(status: StatusCode, json: &str) -> Response {
+    let mut headers = Headers::new();
+    headers.insert("content-type", "application/json");
+    headers.insert("content-length", json.len().to_string());
+    Response {
+        status,
+        headers,
+        body: json.as_bytes().to_vec(),
+        channel: None,
     }
 }
 
-/// Helper function to remove boilerplate for creating responses with associated headers through a passed html file.
+/// Compresses the body of the response with gzip or deflate if the client accepts it and the body is worth compressing.
 ///
-/// # Errors
+/// `accept_encoding` is the value of the `Accept-Encoding` request header. gzip is preferred over deflate, codings with
+/// `q=0` are not used. Bodies that are not compressible according to `Response::is_compressible` are left as is.
+/// Compressible responses get `Vary: Accept-Encoding`, as their representation depends on the request header.
 ///
-/// Returns an `HttpError` if reading the file fails.
-pub async fn file_response(status: StatusCode, path: &str) -> Result<Response, HttpError> {
+/// # Examples
+/// ```
+/// use httpserver::http::response::{Response, compress_response};
+/// let response = Response::builder().header("content-type", "text/html").body("a".repeat(2048)).build();
+/// let response = compress_response(response, "gzip, deflate", 1024);
+/// assert_eq!(response.headers.get("content-encoding"), Some("gzip"));
+/// ```
+#[must_use]
+pub fn compress_response(
+    mut response: Response,
+    accept_encoding: &str,
+    min_size: usize,
+) -> Response {
+    if !response.is_compressible(min_size) {
+        return response;
+    }
+    response.headers.append("vary", "Accept-Encoding");
+    let Some(encoding) = preferred_encoding(accept_encoding) else {
+        return response;
+    };
+
+    let compressed = if encoding == "gzip" {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&response.body)
+            .and_then(|()| encoder.finish())
+    } else {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&response.body)
+            .and_then(|()| encoder.finish())
+    };
+    // Writing to a Vec cannot fail, the body is sent uncompressed if it does anyway.
+    if let Ok(body) = compressed {
+        response.headers.insert("content-encoding", encoding);
+        response
+            .headers
+            .insert("content-length", body.len().to_string());
+        response.body = body;
+    }
+    response
+}
+
+/// Picks the content coding to compress with from the value of an `Accept-Encoding` header.
+///
+/// Returns None if neither gzip nor deflate are acceptable.
+fn preferred_encoding(accept_encoding: &str) -> Option<&'static str> {
+    let mut wildcard = false;
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';').map(str::trim);
+        let coding = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if quality <= 0.0 {
+            rejected.push(coding);
+        } else if coding == "*" {
+            wildcard = true;
+        } else {
+            accepted.push(coding);
+        }
+    }
+    // An explicitly rejected coding is not used, even if a wildcard accepts any other.
+    ["gzip", "deflate"].into_iter().find(|encoding| {
+        !rejected.iter().any(|coding| coding == encoding)
+            && (wildcard || accepted.iter().any(|coding| coding == encoding))
+    })
+}
+
+/// Helper function to remove boilerplate for creating redirect responses to the passed location.
+///
+/// Use `307 Temporary Redirect` or `308 Permanent Redirect` when the client has to repeat the request with the same method
+/// and body, for example when an API endpoint moved. `301`, `302` and `303` allow the client to switch to `GET`, see
+/// `StatusCode::preserves_method`.
+///
+/// # Examples
+/// ```
+/// use httpserver::http::response::{StatusCode, redirect_response};
+/// let response = redirect_response(StatusCode::PermanentRedirect, "/api/v2/orders");
+/// assert_eq!(response.headers.get("location"), Some("/api/v2/orders"));
+/// ```
+#[must_use]
+pub fn redirect_response(status: StatusCode, location: &str) -> Response {
     let mut headers = Headers::new();
-    let body = read_to_string(path).await?;
-    headers.insert("content-type", "text/html");
-    headers.insert("content-length", body.len().to_string());
-    Ok(Response {
+    headers.insert("location", location);
+    headers.insert("content-length", "0");
+    Response {
         status,
         headers,
-        body: body.as_bytes().to_vec(),
-    })
+        body: Vec::new(),
+        channel: None,
+    }
+}
+
+/// Helper function to remove boilerplate for creating responses with associated headers through a passed file.
+///
+/// Serves the file like `fs::file_response`, so the response carries `Accept-Ranges: bytes`. The `Range` header of the
+/// request is only honoured for `StatusCode::Ok`, a response with another status always contains the entire file.
+///
+/// # Errors
+///
+/// Returns an `HttpError` if reading the file fails.
+pub async fn file_response(
+    request: &Request,
+    status: StatusCode,
+    path: &str,
+) -> Result<Response, HttpError> {
+    let range = if status == StatusCode::Ok {
+        request.headers.get("range")
+    } else {
+        None
+    };
+    let mut response = fs::file_range_response(Path::new(path), range).await?;
+    if response.status == StatusCode::Ok {
+        response.status = status;
+    }
+    Ok(response)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::Read,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use flate2::read::ZlibDecoder;
+    use tokio::io::{AsyncWriteExt, duplex};
+
     use crate::{
         http::headers::Headers,
+        http::limits::ParseLimits,
+        http::request::{HttpError, Request},
         http::response::{
-            StatusCode, write_chunked_body, write_final_body_chunk, write_headers,
-            write_status_line,
+            Response, StatusCode, compress_response, file_response, json_response,
+            redirect_response, response_framing_headers, response_from_reader,
+            response_from_reader_with_limits, write_chunked_body, write_final_body_chunk,
+            write_headers, write_response, write_status_line,
         },
     };
 
@@ -223,6 +1181,11 @@ mod tests {
             (StatusCode::BadRequest, "Bad Request"),
             (StatusCode::NotFound, "Not Found"),
             (StatusCode::InternalServerError, "Internal Server Error"),
+            (StatusCode::NoContent, "No Content"),
+            (StatusCode::SeeOther, "See Other"),
+            (StatusCode::MethodNotAllowed, "Method Not Allowed"),
+            (StatusCode::UnsupportedMediaType, "Unsupported Media Type"),
+            (StatusCode::TooManyRequests, "Too Many Requests"),
         ];
 
         for (method, expected) in valid_methods {
@@ -230,6 +1193,230 @@ mod tests {
         }
     }
 
+    #[test]
+    fn from_u16_roundtrips_every_status_code() {
+        for code in 100..600 {
+            if let Some(status) = StatusCode::from_u16(code) {
+                assert_eq!(status as u16, code);
+                assert_eq!(status.to_string(), code.to_string());
+            }
+        }
+        assert_eq!(StatusCode::from_u16(401), Some(StatusCode::Unauthorized));
+        assert_eq!(StatusCode::from_u16(502), Some(StatusCode::BadGateway));
+        assert_eq!(StatusCode::from_u16(418), None);
+    }
+
+    #[test]
+    fn status_classes_match_code_ranges() {
+        assert!(StatusCode::Continue.is_informational());
+        assert!(StatusCode::NoContent.is_success());
+        assert!(StatusCode::TemporaryRedirect.is_redirection());
+        assert!(StatusCode::TooManyRequests.is_client_error());
+        assert!(StatusCode::BadGateway.is_server_error());
+        assert!(!StatusCode::Ok.is_client_error());
+        assert!(!StatusCode::NotFound.is_server_error());
+    }
+
+    #[tokio::test]
+    async fn permanent_redirect_keeps_method_and_body() {
+        let response = redirect_response(StatusCode::PermanentRedirect, "/api/v2/orders");
+        assert!(response.status.preserves_method());
+
+        let mut buffer = Vec::new();
+        write_response(&mut buffer, response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "HTTP/1.1 308 Permanent Redirect\r\nlocation: /api/v2/orders\r\ncontent-length: 0\r\n\r\n"
+        );
+
+        for status in [
+            StatusCode::MovedPermanently,
+            StatusCode::Found,
+            StatusCode::SeeOther,
+        ] {
+            assert!(!status.preserves_method());
+        }
+        assert!(StatusCode::TemporaryRedirect.preserves_method());
+    }
+
+    #[tokio::test]
+    async fn write_response_produces_exact_bytes() {
+        let mut buffer = Vec::new();
+        let response = Response {
+            status: StatusCode::Created,
+            headers: Headers::from_pairs(&[("content-length", "5")]),
+            body: b"hello".to_vec(),
+            channel: None,
+        };
+
+        write_response(&mut buffer, response).await.unwrap();
+
+        assert_eq!(
+            buffer,
+            b"HTTP/1.1 201 Created\r\ncontent-length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[tokio::test]
+    async fn unsupported_transfer_encoding_is_not_written() {
+        for encoding in ["gzip", "chunked, gzip", "chunked, chunked"] {
+            let mut buffer = Vec::new();
+            let response = Response::builder()
+                .header("transfer-encoding", encoding)
+                .body("hello")
+                .build();
+
+            let result = write_response(&mut buffer, response).await;
+
+            assert!(
+                matches!(result, Err(HttpError::UnsupportedTransferEncoding(_))),
+                "{encoding}"
+            );
+            assert!(buffer.is_empty(), "{encoding}");
+        }
+    }
+
+    #[tokio::test]
+    async fn declared_chunked_transfer_encoding_frames_body_as_chunks() {
+        let mut buffer = Vec::new();
+        let response = Response::builder()
+            .header("transfer-encoding", "gzip, chunked")
+            .body("hello")
+            .build();
+
+        write_response(&mut buffer, response).await.unwrap();
+
+        assert_eq!(
+            buffer,
+            b"HTTP/1.1 200 OK\r\ntransfer-encoding: gzip, chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn builder_sets_content_length_from_body() {
+        let response = Response::builder()
+            .status(StatusCode::NotFound)
+            .header("Content-Type", "text/plain")
+            .body("not here")
+            .build();
+
+        assert_eq!(response.status, StatusCode::NotFound);
+        assert_eq!(response.headers.get("content-type"), Some("text/plain"));
+        assert_eq!(response.headers.get("content-length"), Some("8"));
+        assert_eq!(response.body, b"not here");
+    }
+
+    #[test]
+    fn framing_headers_for_known_length() {
+        let headers = response_framing_headers(Some(1024));
+
+        assert_eq!(headers.get("content-length"), Some("1024"));
+        assert_eq!(headers.get("transfer-encoding"), None);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn framing_headers_for_unknown_length() {
+        let headers = response_framing_headers(None);
+
+        assert_eq!(headers.get("transfer-encoding"), Some("chunked"));
+        assert_eq!(headers.get("content-length"), None);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn json_response_sets_content_type_and_length() {
+        let response = json_response(StatusCode::Ok, r#"{"name":"Café"}"#);
+
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some("application/json")
+        );
+        assert_eq!(response.headers.get("content-length"), Some("16"));
+        assert_eq!(response.body, r#"{"name":"Café"}"#.as_bytes());
+    }
+
+    #[test]
+    fn download_quotes_ascii_filename() {
+        let response = Response::download(b"a,b".to_vec(), "q1 \"final\".csv", "text/csv");
+
+        assert_eq!(
+            response.headers.get("content-disposition"),
+            Some("attachment; filename=\"q1 \\\"final\\\".csv\"")
+        );
+        assert_eq!(response.headers.get("content-type"), Some("text/csv"));
+        assert_eq!(response.headers.get("content-length"), Some("3"));
+    }
+
+    #[test]
+    fn download_encodes_utf8_filename() {
+        let response = Response::download("data", "Übersicht März.pdf", "application/pdf");
+
+        assert_eq!(
+            response.headers.get("content-disposition"),
+            Some(
+                "attachment; filename=\"_bersicht M_rz.pdf\"; filename*=UTF-8''%C3%9Cbersicht%20M%C3%A4rz.pdf"
+            )
+        );
+    }
+
+    #[test]
+    fn service_unavailable_sets_retry_after_in_seconds() {
+        let response = Response::service_unavailable(Some(Duration::from_millis(1500).into()));
+
+        assert_eq!(response.status, StatusCode::ServiceUnavailable);
+        assert_eq!(response.headers.get("retry-after"), Some("2"));
+
+        let response = Response::service_unavailable(None);
+        assert_eq!(response.headers.get("retry-after"), None);
+    }
+
+    #[test]
+    fn compressed_body_round_trips_through_decoder() {
+        let body = "{\"drink\":\"coffee\"}".repeat(200);
+        let response = json_response(StatusCode::Ok, &body);
+
+        let compressed = compress_response(response, "gzip;q=0, *", 1024);
+
+        assert_eq!(compressed.headers.get("content-encoding"), Some("deflate"));
+        assert_eq!(
+            compressed.headers.get("content-length"),
+            Some(compressed.body.len().to_string().as_str())
+        );
+        let mut decoded = String::new();
+        ZlibDecoder::new(compressed.body.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        let small = compress_response(json_response(StatusCode::Ok, "{}"), "gzip", 1024);
+        assert_eq!(small.headers.get("content-encoding"), None);
+        assert_eq!(small.body, b"{}");
+    }
+
+    #[test]
+    fn too_many_requests_sets_retry_after_as_date() {
+        let at = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        let response = Response::too_many_requests(Some(at.into()));
+
+        assert_eq!(response.status, StatusCode::TooManyRequests);
+        assert_eq!(
+            response.headers.get("retry-after"),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+    }
+
+    #[test]
+    fn builder_keeps_explicit_content_length() {
+        let response = Response::builder()
+            .header("Content-Length", "0")
+            .body(vec![1, 2, 3])
+            .build();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.headers.get("content-length"), Some("0"));
+    }
+
     #[tokio::test]
     async fn write_status_line_produces_correct_http_line() {
         let mut buffer = Vec::new();
@@ -254,6 +1441,118 @@ mod tests {
         assert_eq!(buffer, expected);
     }
 
+    #[tokio::test]
+    async fn file_response_advertises_range_support() {
+        let path = "examples/full_example/static/hello.html";
+        let request = Request::from_slice(
+            b"GET /hello.html HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-3\r\n\r\n",
+        )
+        .unwrap();
+
+        let partial = file_response(&request, StatusCode::Ok, path).await.unwrap();
+        let not_found = file_response(&request, StatusCode::NotFound, path)
+            .await
+            .unwrap();
+
+        assert_eq!(partial.headers.get("accept-ranges"), Some("bytes"));
+        assert_eq!(partial.status, StatusCode::PartialContent);
+        assert_eq!(partial.body.len(), 4);
+        assert_eq!(not_found.status, StatusCode::NotFound);
+        assert_eq!(
+            not_found.body.len(),
+            usize::try_from(std::fs::metadata(path).unwrap().len()).unwrap()
+        );
+        assert_eq!(
+            not_found.headers.get("content-type"),
+            Some("text/html; charset=utf-8")
+        );
+    }
+
+    #[test]
+    fn small_bodies_are_not_compressible() {
+        let small = Response::builder()
+            .header("content-type", "text/plain")
+            .body("tiny")
+            .build();
+        let large = Response::builder()
+            .header("content-type", "text/plain")
+            .body("a".repeat(2048))
+            .build();
+
+        assert!(!small.is_compressible(1024));
+        assert!(large.is_compressible(1024));
+    }
+
+    #[test]
+    fn compressed_media_types_are_not_compressible() {
+        for content_type in ["image/png", "video/mp4", "application/zip"] {
+            let response = Response::builder()
+                .header("content-type", content_type)
+                .body(vec![0u8; 2048])
+                .build();
+            assert!(!response.is_compressible(1024), "{content_type}");
+        }
+        let svg = Response::builder()
+            .header("content-type", "image/svg+xml")
+            .body(vec![b'<'; 2048])
+            .build();
+        assert!(svg.is_compressible(1024));
+    }
+
+    #[test]
+    fn partial_content_is_not_compressible() {
+        let partial = Response::builder()
+            .status(StatusCode::PartialContent)
+            .header("content-type", "text/plain")
+            .header("content-range", "bytes 0-2047/4096")
+            .body("a".repeat(2048))
+            .build();
+        let unsatisfiable = Response::builder()
+            .status(StatusCode::RangeNotSatisfiable)
+            .header("content-type", "text/plain")
+            .header("content-range", "bytes */4096")
+            .body("a".repeat(2048))
+            .build();
+
+        assert!(!partial.is_compressible(1024));
+        assert!(!unsatisfiable.is_compressible(1024));
+    }
+
+    #[tokio::test]
+    async fn write_headers_keeps_insertion_order() {
+        let mut buffer = Vec::new();
+        let mut headers = Headers::new();
+        headers.insert("x-first", "1");
+        headers.insert("content-type", "text/plain");
+        headers.insert("a-last", "3");
+
+        write_headers(&mut buffer, &mut headers).await.unwrap();
+
+        assert_eq!(
+            buffer,
+            b"x-first: 1\r\ncontent-type: text/plain\r\na-last: 3\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_set_cookie_headers_are_written_as_separate_lines() {
+        let mut buffer = Vec::new();
+        let mut headers = Headers::new();
+        headers
+            .parse_header(
+                "Set-Cookie: id=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\nSet-Cookie: theme=dark\r\n\r\n",
+            )
+            .unwrap();
+
+        write_headers(&mut buffer, &mut headers).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "set-cookie: id=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\nset-cookie: theme=dark\r\n\r\n"
+        );
+        assert_eq!(headers.get_all("set-cookie").count(), 2);
+    }
+
     #[tokio::test]
     async fn write_chunked_bodies_formats_body() {
         let mut buffer = Vec::new();
@@ -295,4 +1594,124 @@ mod tests {
 
         assert_eq!(buffer, expected.as_bytes());
     }
+
+    #[tokio::test]
+    async fn response_from_reader_parses_content_length_body() {
+        let mut input: &[u8] =
+            b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 11\r\n\r\nhello world";
+
+        let response = response_from_reader(&mut input).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.headers.get("content-type"), Some("text/plain"));
+        assert_eq!(response.body, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn response_from_reader_decodes_chunked_body() {
+        let mut input: &[u8] = b"HTTP/1.1 404 Not Found\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            5\r\nhello\r\n\
+            6\r\n world\r\n\
+            0\r\n\
+            server-timing: db;dur=1\r\n\
+            \r\n";
+
+        let response = response_from_reader(&mut input).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::NotFound);
+        assert_eq!(response.headers.get("transfer-encoding"), Some("chunked"));
+        assert_eq!(response.body, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn upstream_closing_mid_chunk_should_throw_unexpectedeof() {
+        let truncated: [&[u8]; 3] = [
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\nA\r\n wor",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n",
+        ];
+
+        for data in truncated {
+            let (mut upstream, mut proxy) = duplex(64);
+            tokio::spawn(async move {
+                upstream.write_all(data).await.unwrap();
+                // Dropping the stream closes the connection without the terminating chunk.
+            });
+
+            let result = response_from_reader(&mut proxy).await;
+
+            assert!(
+                matches!(result, Err(HttpError::UnexpectedEOF)),
+                "{}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+    #[tokio::test]
+    async fn oversized_response_chunk_should_throw_instead_of_panicking() {
+        let data: &[u8] =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nFFFFFFFFFFFFFFFF\r\n";
+
+        let result = response_from_reader(&mut &data[..]).await;
+        assert!(matches!(result, Err(HttpError::ContentTooLarge)));
+
+        let unlimited = ParseLimits {
+            max_chunk_size: usize::MAX,
+            max_request_size: usize::MAX,
+            ..ParseLimits::default()
+        };
+        let result = response_from_reader_with_limits(&mut &data[..], &unlimited).await;
+        assert!(matches!(result, Err(HttpError::InvalidBodyLength)));
+    }
+
+    #[tokio::test]
+    async fn oversized_response_should_throw_contenttoolarge() {
+        let limits = ParseLimits {
+            max_header_size: 64,
+            max_request_size: 8,
+            ..ParseLimits::default()
+        };
+        let endless_headers = format!("HTTP/1.1 200 OK\r\n{}", "x-filler: a\r\n".repeat(20));
+        let oversized: [&[u8]; 4] = [
+            endless_headers.as_bytes(),
+            b"HTTP/1.1 200 OK\r\ncontent-length: 9\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\n\r\nhello world",
+        ];
+
+        for data in oversized {
+            let result = response_from_reader_with_limits(&mut &data[..], &limits).await;
+
+            assert!(
+                matches!(result, Err(HttpError::ContentTooLarge)),
+                "{}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bodiless_statuses_should_not_wait_for_the_connection_to_close() {
+        let heads: [&[u8]; 3] = [
+            b"HTTP/1.1 101 Switching Protocols\r\nupgrade: websocket\r\n\r\n",
+            b"HTTP/1.1 204 No Content\r\n\r\n",
+            b"HTTP/1.1 304 Not Modified\r\n\r\n",
+        ];
+
+        for head in heads {
+            let (mut upstream, mut proxy) = duplex(64);
+            upstream.write_all(head).await.unwrap();
+
+            let response =
+                tokio::time::timeout(Duration::from_secs(1), response_from_reader(&mut proxy))
+                    .await
+                    .expect("a bodiless response should not wait for the peer to close")
+                    .unwrap();
+
+            assert!(response.body.is_empty());
+            drop(upstream);
+        }
+    }
 }
@@ -0,0 +1,127 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncRead, AsyncWriteExt, ReadBuf},
+};
+
+/// Counter making the names of spill files unique within the process.
+static NEXT_SPILL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary file holding the part of a request body that exceeded the in-memory threshold.
+///
+/// The file is removed once the struct is dropped.
+#[derive(Debug)]
+pub struct SpillFile {
+    /// The location of the file in the temporary directory.
+    path: PathBuf,
+    /// The handle data is appended through.
+    file: File,
+    /// The amount of bytes written to the file.
+    len: usize,
+}
+
+impl SpillFile {
+    /// Creates a new, empty spill file in the temporary directory of the system, only accessible by the current user.
+    ///
+    /// # Errors
+    ///
+    /// Throws an `io::Error` if the file cannot be created.
+    pub async fn create() -> io::Result<Self> {
+        let id = NEXT_SPILL_ID.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("httpserver-body-{}-{id}", process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // The body may contain sensitive data, so other users must not be able to read it from the shared directory.
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options.open(&path).await?;
+        Ok(Self { path, file, len: 0 })
+    }
+
+    /// Appends the passed data to the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// Throws an `io::Error` if writing to the file fails.
+    pub async fn append(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data).await?;
+        self.file.flush().await?;
+        self.len += data.len();
+        Ok(())
+    }
+
+    /// Returns the amount of bytes stored in the file.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no data was written to the file yet.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the location of the file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // The file may already be gone if the temporary directory was cleaned up, which is fine.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reader over a request body that reads the in-memory part first and the spilled part from disk afterwards.
+#[derive(Debug)]
+pub struct BodyReader<'a> {
+    /// The part of the body that is not read yet and kept in memory.
+    memory: &'a [u8],
+    /// A separate handle to the spill file, if the body was spilled.
+    spill: Option<File>,
+}
+
+impl<'a> BodyReader<'a> {
+    /// Creates a reader over the passed in-memory data, followed by the contents of the spill file if present.
+    ///
+    /// # Errors
+    ///
+    /// Throws an `io::Error` if the spill file cannot be opened for reading.
+    pub async fn new(memory: &'a [u8], spill: Option<&SpillFile>) -> io::Result<Self> {
+        let spill = match spill {
+            Some(spill) => Some(File::open(spill.path()).await?),
+            None => None,
+        };
+        Ok(Self { memory, spill })
+    }
+}
+
+impl AsyncRead for BodyReader<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.memory.is_empty() {
+            let n = self.memory.len().min(buf.remaining());
+            buf.put_slice(&self.memory[..n]);
+            self.memory = &self.memory[n..];
+            return Poll::Ready(Ok(()));
+        }
+        self.spill.as_mut().map_or(Poll::Ready(Ok(())), |file| {
+            Pin::new(file).poll_read(cx, buf)
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use crate::http::{
+    headers::Headers,
+    request::HttpError,
+    response::{ChunkedResponseWriter, Response, StatusCode},
+};
+
+/// Writes Server-Sent Events to the body of a `text/event-stream` response.
+///
+/// The response is sent with `Transfer-Encoding: chunked`, see `Response::chunked`, and every event is sent as a separate
+/// chunk. The stream ends once the writer is dropped.
+///
+/// # Examples
+/// ```
+/// use httpserver::{http::sse::SseWriter, runtime::router::Router};
+/// let mut router = Router::new();
+/// router.route("/events", |_req| async {
+///     let (response, events) = SseWriter::new();
+///     tokio::spawn(async move {
+///         let _ = events.send_event(Some("update"), "{\"cpu\":42}").await;
+///     });
+///     response
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct SseWriter {
+    /// The writer of the chunked response body the events are sent through.
+    writer: ChunkedResponseWriter,
+}
+
+impl SseWriter {
+    /// Creates the `200 OK` event stream response for the handler to return, along with the writer for the events.
+    #[must_use]
+    pub fn new() -> (Response, Self) {
+        let mut headers = Headers::new();
+        headers.insert("content-type", "text/event-stream");
+        headers.insert("cache-control", "no-cache");
+        let (response, writer) = Response::chunked(StatusCode::Ok, headers);
+        (response, Self { writer })
+    }
+
+    /// Sends an event with an optional event type, writing every line of `data` as a separate `data:` field.
+    ///
+    /// Line breaks in the event type are removed, as they would end the field and let the rest be read as another one.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` with `BrokenPipe` if the response is no longer being written, such as after the client disconnected.
+    pub async fn send_event(&self, event: Option<&str>, data: &str) -> Result<(), HttpError> {
+        let mut message = String::new();
+        if let Some(event) = event {
+            message.push_str("event: ");
+            message.extend(event.chars().filter(|c| !matches!(c, '\r' | '\n')));
+            message.push('\n');
+        }
+        for line in data.split('\n') {
+            message.push_str("data: ");
+            message.push_str(line.strip_suffix('\r').unwrap_or(line));
+            message.push('\n');
+        }
+        message.push('\n');
+        self.writer.write(message).await
+    }
+
+    /// Sends a comment, which clients ignore, such as a ping keeping the connection open through idle proxies.
+    ///
+    /// Every line of the comment is sent as a separate comment line, so it cannot end the comment early.
+    ///
+    /// # Errors
+    ///
+    /// Throws `HttpError::Io` with `BrokenPipe` if the response is no longer being written, such as after the client disconnected.
+    pub async fn send_comment(&self, comment: &str) -> Result<(), HttpError> {
+        let mut message = String::new();
+        for line in comment.split('\n') {
+            message.push_str(": ");
+            message.push_str(line.strip_suffix('\r').unwrap_or(line));
+            message.push('\n');
+        }
+        message.push('\n');
+        self.writer.write(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{
+        response::{response_from_reader, write_response},
+        sse::SseWriter,
+    };
+
+    #[tokio::test]
+    async fn events_are_formatted_as_event_stream() {
+        let (response, events) = SseWriter::new();
+        tokio::spawn(async move {
+            events
+                .send_event(Some("update"), "{\"cpu\":42}\nsecond line")
+                .await
+                .unwrap();
+            events.send_event(None, "plain").await.unwrap();
+            events.send_comment("ping").await.unwrap();
+        });
+
+        let mut written = Vec::new();
+        write_response(&mut written, response).await.unwrap();
+        let response = response_from_reader(&mut written.as_slice()).await.unwrap();
+
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some("text/event-stream")
+        );
+        assert_eq!(response.headers.get("transfer-encoding"), Some("chunked"));
+        assert_eq!(
+            String::from_utf8(response.body).unwrap(),
+            "event: update\n\
+            data: {\"cpu\":42}\n\
+            data: second line\n\
+            \n\
+            data: plain\n\
+            \n\
+            : ping\n\
+            \n"
+        );
+    }
+
+    #[tokio::test]
+    async fn line_breaks_cannot_inject_fields() {
+        let (response, events) = SseWriter::new();
+        tokio::spawn(async move {
+            events
+                .send_event(Some("update\r\ndata: forged"), "real")
+                .await
+                .unwrap();
+            events.send_comment("ping\ndata: forged").await.unwrap();
+        });
+
+        let mut written = Vec::new();
+        write_response(&mut written, response).await.unwrap();
+        let response = response_from_reader(&mut written.as_slice()).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(response.body).unwrap(),
+            "event: updatedata: forged\n\
+            data: real\n\
+            \n\
+            : ping\n\
+            : data: forged\n\
+            \n"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::http::{
+    headers::Headers,
+    request::{HttpError, Request},
+    request_line::Method,
+    response::{Response, StatusCode, write_response},
+};
+
+/// The GUID appended to the client key before hashing, as defined by RFC 6455.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Completes the WebSocket opening handshake for the passed request by writing `101 Switching Protocols` to the stream.
+///
+/// The stream is handed back afterwards, reading and writing frames is left to the caller. Routes registered with
+/// `Router::websocket` are answered this way by the server, which then hands the connection to their handler.
+///
+/// # Errors
+///
+/// Throws `HttpError::InvalidWebSocketHandshake` if the request is not a valid opening handshake, in which case nothing is
+/// written. Throws an `HttpError` if writing the response fails.
+pub async fn accept<W: AsyncWrite + Unpin>(
+    request: &Request,
+    mut stream: W,
+) -> Result<W, HttpError> {
+    let key = validate_handshake(request)?;
+
+    let mut headers = Headers::new();
+    headers.insert("upgrade", "websocket");
+    headers.insert("connection", "Upgrade");
+    headers.insert("sec-websocket-accept", accept_key(key));
+    let response = Response {
+        status: StatusCode::SwitchingProtocols,
+        headers,
+        body: Vec::new(),
+        channel: None,
+    };
+    write_response(&mut stream, response).await?;
+    Ok(stream)
+}
+
+/// A stream that can be handed to a WebSocket handler.
+trait UpgradedIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> UpgradedIo for T {}
+
+/// A connection taken over by a WebSocket handler after the `101 Switching Protocols` response, see `Router::websocket`.
+///
+/// Reads first return the bytes the client sent right after the opening handshake, which the server had already received.
+pub struct Upgraded {
+    /// The bytes received after the opening handshake that are not read yet.
+    pending: Vec<u8>,
+    /// The position of the next unread byte in `pending`.
+    position: usize,
+    /// The connection to the client.
+    stream: Box<dyn UpgradedIo>,
+}
+
+impl Upgraded {
+    /// Wraps the passed connection, returning the already received bytes before reading from it.
+    pub fn new(
+        stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        pending: Vec<u8>,
+    ) -> Self {
+        Self {
+            pending,
+            position: 0,
+            stream: Box::new(stream),
+        }
+    }
+}
+
+impl fmt::Debug for Upgraded {
+    /// Prints the amount of unread received bytes, as the wrapped stream is not printable.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upgraded")
+            .field("pending", &(self.pending.len() - self.position))
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for Upgraded {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.position < self.pending.len() {
+            let n = (self.pending.len() - self.position).min(buf.remaining());
+            let start = self.position;
+            buf.put_slice(&self.pending[start..start + n]);
+            self.position += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Computes the `Sec-WebSocket-Accept` value for the passed `Sec-WebSocket-Key`.
+///
+/// # Examples
+/// ```
+/// let accept = httpserver::http::websocket::accept_key("dGhlIHNhbXBsZSBub25jZQ==");
+/// assert_eq!(accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+/// ```
+#[must_use]
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(HANDSHAKE_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+/// Checks the headers of an opening handshake, returning the `Sec-WebSocket-Key`.
+///
+/// # Errors
+///
+/// Throws `HttpError::InvalidWebSocketHandshake` naming the first requirement the request does not meet.
+fn validate_handshake(request: &Request) -> Result<&str, HttpError> {
+    if request.request_line.method != Method::Get {
+        return Err(HttpError::InvalidWebSocketHandshake("method is not GET"));
+    }
+    if !has_token(&request.headers, "upgrade", "websocket") {
+        return Err(HttpError::InvalidWebSocketHandshake(
+            "missing upgrade: websocket",
+        ));
+    }
+    if !has_token(&request.headers, "connection", "upgrade") {
+        return Err(HttpError::InvalidWebSocketHandshake(
+            "missing connection: upgrade",
+        ));
+    }
+    if request.headers.get("sec-websocket-version") != Some("13") {
+        return Err(HttpError::InvalidWebSocketHandshake(
+            "unsupported sec-websocket-version",
+        ));
+    }
+    let key = request
+        .headers
+        .get("sec-websocket-key")
+        .map(str::trim)
+        .ok_or(HttpError::InvalidWebSocketHandshake(
+            "missing sec-websocket-key",
+        ))?;
+    // The key is a base64 encoded 16 byte nonce.
+    if STANDARD.decode(key).map_or(true, |nonce| nonce.len() != 16) {
+        return Err(HttpError::InvalidWebSocketHandshake(
+            "malformed sec-websocket-key",
+        ));
+    }
+    Ok(key)
+}
+
+/// Whether the comma separated header value contains the passed token, ignoring case.
+fn has_token(headers: &Headers, key: &str, token: &str) -> bool {
+    headers
+        .get_list(key)
+        .iter()
+        .any(|item| item.eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{
+        request::{HttpError, Request},
+        websocket::{accept, accept_key},
+    };
+
+    const HANDSHAKE: &str = "GET /chat HTTP/1.1\r\n\
+        Host: server.example.com\r\n\
+        Upgrade: websocket\r\n\
+        Connection: keep-alive, Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        \r\n";
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn valid_handshake_is_answered_with_switching_protocols() {
+        let request = Request::from_slice(HANDSHAKE.as_bytes()).unwrap();
+
+        let stream = accept(&request, Vec::new()).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(stream).unwrap(),
+            "HTTP/1.1 101 Switching Protocols\r\n\
+            upgrade: websocket\r\n\
+            connection: Upgrade\r\n\
+            sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+            \r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_handshake_writes_nothing() {
+        let request = Request::from_slice(
+            HANDSHAKE
+                .replace("dGhlIHNhbXBsZSBub25jZQ==", "c2hvcnQ=")
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let result = accept(&request, Vec::new()).await;
+
+        assert!(matches!(
+            result,
+            Err(HttpError::InvalidWebSocketHandshake(_))
+        ));
+    }
+}
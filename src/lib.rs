@@ -9,3 +9,5 @@
 pub mod http;
 /// Logic handling runtime logic for a server instance.
 pub mod runtime;
+/// Utilities reusable outside of the server, such as debugging tools.
+pub mod util;
@@ -0,0 +1,14 @@
+use tokio::time::Instant;
+
+/// Information about the connection a request arrived on, made available to handlers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    /// The application protocol negotiated through ALPN during the TLS handshake, if any.
+    pub alpn_protocol: Option<String>,
+    /// Whether the request arrived over TLS, false for plain connections.
+    pub is_secure: bool,
+    /// Whether the scheme in an `X-Forwarded-Proto` header set by a TLS-terminating proxy is trusted over `is_secure`.
+    pub trust_forwarded_proto: bool,
+    /// The point in time the request was parsed and handed to the handler, None before that.
+    pub started_at: Option<Instant>,
+}
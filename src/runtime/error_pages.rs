@@ -0,0 +1,70 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::http::response::{Response, StatusCode, html_response};
+
+/// Custom HTML pages served instead of the built-in ones when the server answers with an error status.
+///
+/// The pages are read once when the server starts and shared by all connections.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorPages {
+    /// The contents of the custom pages by status code
+    pages: Arc<HashMap<u16, String>>,
+}
+
+impl ErrorPages {
+    /// Reads the pages configured as status code to file path pairs, such as `500 = "static/500.html"`.
+    ///
+    /// Entries whose key is not a status code or whose file cannot be read are skipped with a warning, so the built-in
+    /// page is served for them.
+    #[must_use]
+    pub fn load(paths: &HashMap<String, String>) -> Self {
+        let mut pages = HashMap::new();
+        for (status, path) in paths {
+            let Ok(status) = status.parse::<u16>() else {
+                eprintln!("Ignoring error page for invalid status code {status}");
+                continue;
+            };
+            match std::fs::read_to_string(path) {
+                Ok(page) => {
+                    pages.insert(status, page);
+                }
+                Err(e) => {
+                    eprintln!("Using the built-in page for {status}, reading {path} failed: {e}");
+                }
+            }
+        }
+        Self {
+            pages: Arc::new(pages),
+        }
+    }
+
+    /// Creates the HTML response for the passed status, using the custom page if one was loaded and `builtin` otherwise.
+    #[must_use]
+    pub fn response(&self, status: StatusCode, builtin: &str) -> Response {
+        let html = self
+            .pages
+            .get(&(status as u16))
+            .map_or(builtin, String::as_str);
+        html_response(status, html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{http::response::StatusCode, runtime::error_pages::ErrorPages};
+
+    #[test]
+    fn missing_page_falls_back_to_builtin() {
+        let paths = HashMap::from([
+            ("404".to_string(), "does/not/exist.html".to_string()),
+            ("teapot".to_string(), "config.toml".to_string()),
+        ]);
+        let pages = ErrorPages::load(&paths);
+
+        let response = pages.response(StatusCode::NotFound, "<h1>Not Found</h1>");
+
+        assert_eq!(response.body, b"<h1>Not Found</h1>");
+    }
+}
@@ -0,0 +1,89 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::timeout,
+};
+
+/// A limit on the requests handled across all connections at once.
+#[derive(Clone, Debug)]
+pub struct InflightLimiter {
+    /// Semaphore holding one permit per request that may be handled at the same time.
+    semaphore: Arc<Semaphore>,
+    /// How long a request waits for a permit once the limit is reached.
+    queue_timeout: Duration,
+}
+
+impl InflightLimiter {
+    /// Creates a limiter allowing up to `limit` requests to be handled at the same time.
+    ///
+    /// Requests exceeding the limit are queued for up to `queue_timeout`, a zero timeout rejects them immediately.
+    #[must_use]
+    pub fn new(limit: usize, queue_timeout: Duration) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit.min(Semaphore::MAX_PERMITS))),
+            queue_timeout,
+        }
+    }
+
+    /// Returns the amount of requests that can currently be handled without waiting.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Acquires a permit for handling a single request, which is released once it is dropped.
+    ///
+    /// Returns None if no permit became available within the queue timeout.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = Arc::clone(&self.semaphore).try_acquire_owned() {
+            return Some(permit);
+        }
+        if self.queue_timeout.is_zero() {
+            return None;
+        }
+        timeout(
+            self.queue_timeout,
+            Arc::clone(&self.semaphore).acquire_owned(),
+        )
+        .await
+        .ok()?
+        .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::runtime::inflight::InflightLimiter;
+
+    #[tokio::test]
+    async fn saturated_limiter_without_queue_rejects_immediately() {
+        let limiter = InflightLimiter::new(1, Duration::ZERO);
+
+        let permit = limiter.acquire().await.unwrap();
+        assert!(limiter.acquire().await.is_none());
+
+        drop(permit);
+        assert_eq!(limiter.available(), 1);
+        assert!(limiter.acquire().await.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_request_gets_released_permit_or_times_out() {
+        let limiter = InflightLimiter::new(1, Duration::from_secs(5));
+        let permit = limiter.acquire().await.unwrap();
+
+        assert!(limiter.acquire().await.is_none());
+
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.is_some() }
+        });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        drop(permit);
+
+        assert!(queued.await.unwrap());
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::http::{request_line::RequestLine, response::StatusCode};
+
+/// Counter making request ids unique within the process.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Writes one line per handled request to a sink, such as stdout.
+///
+/// Connections only send finished lines through a channel, a dedicated thread owning the sink writes them one after
+/// another. Lines of concurrent connections are therefore never interleaved, no matter how the sink splits up writes.
+#[derive(Clone, Debug)]
+pub struct AccessLog {
+    /// The sending half of the channel to the logging thread
+    sender: Sender<String>,
+}
+
+impl AccessLog {
+    /// Starts the logging thread writing to the passed sink.
+    ///
+    /// The thread ends once all clones of the returned log are dropped, or once writing to the sink fails.
+    #[must_use]
+    pub fn new(sink: impl Write + Send + 'static) -> Self {
+        Self {
+            sender: spawn_writer(sink),
+        }
+    }
+
+    /// Records a handled request as `METHOD target status duration`, with the duration in milliseconds.
+    pub fn record(&self, request_line: &RequestLine, status: StatusCode, duration: Duration) {
+        let line = format!(
+            "{} {} {status} {:.1}ms\n",
+            request_line.method,
+            request_line.request_target,
+            duration.as_secs_f64() * 1000.0
+        );
+        // The logging thread only stops after a failed write, in which case the line is dropped.
+        let _ = self.sender.send(line);
+    }
+}
+
+/// Writes the errors behind error responses generated by the server to a sink, such as stderr.
+///
+/// Every line starts with the id of the request in brackets, which the response carries in its `X-Request-Id` header.
+/// Lines are written by a dedicated thread like the ones of `AccessLog`.
+#[derive(Clone, Debug)]
+pub struct ErrorLog {
+    /// The sending half of the channel to the logging thread
+    sender: Sender<String>,
+}
+
+impl ErrorLog {
+    /// Starts the logging thread writing to the passed sink.
+    ///
+    /// The thread ends once all clones of the returned log are dropped, or once writing to the sink fails.
+    #[must_use]
+    pub fn new(sink: impl Write + Send + 'static) -> Self {
+        Self {
+            sender: spawn_writer(sink),
+        }
+    }
+
+    /// Records the error as `[request id] error`.
+    pub fn record(&self, request_id: &str, error: impl Display) {
+        // The logging thread only stops after a failed write, in which case the line is dropped.
+        let _ = self.sender.send(format!("[{request_id}] {error}\n"));
+    }
+}
+
+/// Creates a new id identifying a request in logs, unique within the running process.
+///
+/// # Examples
+/// ```
+/// use httpserver::runtime::log::next_request_id;
+/// assert_ne!(next_request_id(), next_request_id());
+/// ```
+#[must_use]
+pub fn next_request_id() -> String {
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{id:08x}", process::id())
+}
+
+/// Starts a thread writing every line received through the returned sender to the passed sink.
+fn spawn_writer(sink: impl Write + Send + 'static) -> Sender<String> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        let mut sink = sink;
+        for line in receiver {
+            if let Err(e) = sink.write_all(line.as_bytes()).and_then(|()| sink.flush()) {
+                eprintln!("Stopped writing the log: {e}");
+                break;
+            }
+        }
+    });
+    sender
+}
@@ -0,0 +1,111 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+/// A limit on the bytes of request bodies buffered across all connections at once.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    /// The amount of bytes currently reserved by requests.
+    in_use: Arc<AtomicUsize>,
+    /// The amount of bytes that may be reserved at the same time.
+    limit: usize,
+}
+
+/// RAII guard for the bytes reserved by a single request, released once it is dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: MemoryBudget,
+    bytes: usize,
+}
+
+impl MemoryBudget {
+    /// Creates a budget allowing up to `limit` bytes to be reserved at the same time.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            in_use: Arc::new(AtomicUsize::new(0)),
+            limit,
+        }
+    }
+
+    /// Returns the amount of bytes currently reserved.
+    #[must_use]
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::SeqCst)
+    }
+
+    /// Reserves the passed amount of bytes.
+    ///
+    /// Returns None if the reservation would exceed the limit.
+    #[must_use]
+    pub fn try_reserve(&self, bytes: usize) -> Option<MemoryReservation> {
+        let mut reservation = MemoryReservation {
+            budget: self.clone(),
+            bytes: 0,
+        };
+        reservation.grow_to(bytes).then_some(reservation)
+    }
+
+    /// Adds the passed amount of bytes, returning false without reserving anything if the limit would be exceeded.
+    fn add(&self, bytes: usize) -> bool {
+        self.in_use
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_use| {
+                in_use
+                    .checked_add(bytes)
+                    .filter(|total| *total <= self.limit)
+            })
+            .is_ok()
+    }
+}
+
+impl MemoryReservation {
+    /// Grows the reservation to cover at least the passed amount of bytes.
+    ///
+    /// Returns false if the additional bytes would exceed the limit, in which case the reservation is unchanged.
+    #[must_use]
+    pub fn grow_to(&mut self, bytes: usize) -> bool {
+        if bytes <= self.bytes {
+            return true;
+        }
+        if !self.budget.add(bytes - self.bytes) {
+            return false;
+        }
+        self.bytes = bytes;
+        true
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.in_use.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::memory::MemoryBudget;
+
+    #[test]
+    fn reservations_are_released_on_drop() {
+        let budget = MemoryBudget::new(100);
+
+        let first = budget.try_reserve(60).unwrap();
+        assert!(budget.try_reserve(60).is_none());
+        assert_eq!(budget.in_use(), 60);
+
+        drop(first);
+        assert_eq!(budget.in_use(), 0);
+        assert!(budget.try_reserve(60).is_some());
+    }
+
+    #[test]
+    fn growing_past_the_limit_keeps_the_reservation() {
+        let budget = MemoryBudget::new(100);
+        let mut reservation = budget.try_reserve(10).unwrap();
+
+        assert!(reservation.grow_to(100));
+        assert!(!reservation.grow_to(101));
+        assert_eq!(budget.in_use(), 100);
+    }
+}
@@ -1,4 +1,19 @@
+/// Module containing the per-connection context
+pub mod context;
+/// Module containing the custom pages served for error responses
+pub mod error_pages;
+/// Module containing the limit on requests handled at the same time
+pub mod inflight;
+/// Module containing the logs of handled requests and of errors
+pub mod log;
+/// Module containing the memory budget shared by all requests
+pub mod memory;
+/// Module containing the redirect from HTTP to HTTPS
+pub mod redirect;
 /// Module containing the handler
 pub mod router;
 /// Module containing the logic for the server
 pub mod server;
+/// Minimal HTTP client used to test the server end to end
+#[cfg(test)]
+mod test_client;
@@ -0,0 +1,91 @@
+use crate::http::{
+    request::Request,
+    response::{Response, StatusCode, redirect_response},
+};
+
+/// Redirects plain HTTP requests to the same resource over HTTPS.
+///
+/// Uses `308 Permanent Redirect` so clients repeat the request with the same method and body.
+#[derive(Clone, Copy, Debug)]
+pub struct RedirectToHttps {
+    /// The port the HTTPS listener is reachable on
+    port: u16,
+}
+
+impl RedirectToHttps {
+    /// Creates a redirect pointing to the passed HTTPS port.
+    #[must_use]
+    pub const fn new(port: u16) -> Self {
+        Self { port }
+    }
+
+    /// Builds the `https://` URL for the passed request, preserving its path and query.
+    ///
+    /// The port of the `Host` header is replaced by the configured one and omitted if it is the default port 443.
+    ///
+    /// Returns None if the request has no `Host` header.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::runtime::redirect::RedirectToHttps;
+    /// let redirect = RedirectToHttps::new(8443);
+    /// let location = redirect.location("localhost:8080", "/coffee?milk=oat");
+    /// assert_eq!(location, "https://localhost:8443/coffee?milk=oat");
+    /// ```
+    #[must_use]
+    pub fn location(&self, host: &str, origin_form: &str) -> String {
+        let host = strip_port(host);
+        if self.port == 443 {
+            format!("https://{host}{origin_form}")
+        } else {
+            format!("https://{host}:{}{origin_form}", self.port)
+        }
+    }
+
+    /// Creates the redirect response for the passed request.
+    ///
+    /// Returns None if the request has no `Host` header to build the location from.
+    #[must_use]
+    pub fn response(&self, request: &Request) -> Option<Response> {
+        let host = request.headers.get("host")?;
+        let location = self.location(host, &request.request_line.origin_form());
+
+        let mut response = redirect_response(StatusCode::PermanentRedirect, &location);
+        response.headers.insert("connection", "close");
+        Some(response)
+    }
+}
+
+/// Removes the port from a `Host` header value, keeping bracketed IPv6 addresses intact.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    host.split_once(':').map_or(host, |(name, _port)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::redirect::RedirectToHttps;
+
+    #[test]
+    fn location_replaces_host_port() {
+        let redirect = RedirectToHttps::new(8443);
+
+        assert_eq!(redirect.location("[::1]:8080", "/"), "https://[::1]:8443/");
+        assert_eq!(
+            redirect.location("example.com", "/a?b=c"),
+            "https://example.com:8443/a?b=c"
+        );
+    }
+
+    #[test]
+    fn location_omits_default_port() {
+        let redirect = RedirectToHttps::new(443);
+
+        assert_eq!(
+            redirect.location("example.com:80", "/coffee"),
+            "https://example.com/coffee"
+        );
+    }
+}
@@ -1,60 +1,442 @@
-use std::{collections::HashMap, pin::Pin};
+use std::{collections::HashMap, pin::Pin, time::Duration};
 
 use crate::http::{
     request::{HttpError, Request},
+    request_line::{Method, RequestLine, percent_decode_path},
     response::{Response, StatusCode, html_response},
+    websocket::Upgraded,
 };
 
 /// A custom type boxing the Future returned by an async closure to enable storing it in the router.
 type HandlerFn =
     Box<dyn Fn(Request) -> Pin<Box<dyn Future<Output = Response> + Send>> + Send + Sync>;
 
-/// The Router of the application, implemented using a `HashMap` of endpoint / closure pairs.
-pub struct Router(HashMap<String, HandlerFn>);
+/// A custom type boxing the Future returned by a handler taking over a WebSocket connection.
+type WebSocketFn =
+    Box<dyn Fn(Request, Upgraded) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A custom type boxing the filter deciding which authorities `CONNECT` requests may tunnel to.
+type TunnelFilter = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// A custom type boxing the callback observing every completed request.
+type RequestHook = Box<dyn Fn(&RequestLine, StatusCode, Duration) + Send + Sync>;
+
+/// The Router of the application, dispatching requests to the route matching their method and path.
+pub struct Router {
+    /// The registered routes in registration order
+    routes: Vec<Route>,
+    /// The handler called if no route matches, answering `404 Not Found` if None
+    fallback: Option<HandlerFn>,
+    /// The filter for authorities `CONNECT` requests may open a tunnel to, tunneling is disabled if None
+    tunnel: Option<TunnelFilter>,
+    /// The callback observing every completed request, requests are not observed if None
+    request_hook: Option<RequestHook>,
+    /// The routes taking over the connection after a WebSocket opening handshake, in registration order
+    websockets: Vec<WebSocketRoute>,
+}
+
+/// A registered route handing the connection to its handler after the WebSocket opening handshake.
+struct WebSocketRoute {
+    /// The segments of the path pattern
+    segments: Vec<Segment>,
+    handler: WebSocketFn,
+}
+
+/// A registered route containing the handler and an optional body size limit overriding the global one.
+struct Route {
+    /// The method the route accepts, any method if None
+    method: Option<Method>,
+    /// The segments of the path pattern
+    segments: Vec<Segment>,
+    handler: HandlerFn,
+    max_body: Option<usize>,
+}
+
+/// A single `/` separated part of a route pattern.
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    /// A segment that has to match exactly
+    Static(String),
+    /// A `:name` segment matching any non-empty value, which is extracted under the name
+    Param(String),
+}
 
 impl Router {
-    /// Creates and returns a new `HashMap` representing the Router
+    /// Creates and returns a new Router without any routes
     #[must_use]
-    pub fn new() -> Self {
-        Self(HashMap::new())
+    pub const fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            fallback: None,
+            tunnel: None,
+            request_hook: None,
+            websockets: Vec::new(),
+        }
     }
 
-    /// Registers a new route for the router.
+    /// Registers a new route for the router, accepting any method.
+    ///
+    /// Any async closure or function taking the `Request` and returning a `Response` can be used as handler, no handler type is needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::{
+    ///     http::response::{StatusCode, html_response},
+    ///     runtime::router::Router,
+    /// };
+    /// let mut router = Router::new();
+    /// router.route("/hello", |_req| async { html_response(StatusCode::Ok, "<h1>Hello</h1>") });
+    /// ```
     pub fn route<F, Fut>(&mut self, path: &str, handler: F)
     where
         F: Fn(Request) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Response> + Send + 'static,
     {
-        self.0.insert(
-            path.to_string(),
-            Box::new(move |req| Box::pin(handler(req))),
-        );
+        self.insert_route(None, path, None, handler);
     }
 
-    /// Retrieves an optional closure if the passed endpoint is present in the router.
+    /// Registers a new route for the router that only accepts the passed method.
+    ///
+    /// Segments of the pattern starting with `:` match any value, which is available through `Request::param`.
+    /// Static segments take precedence over parameters. Requests matching the path but not the method are answered with
+    /// `405 Method Not Allowed`. Routes for `GET` also answer `HEAD` requests, whose response body the server omits.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::{
+    ///     http::{request_line::Method, response::{StatusCode, html_response}},
+    ///     runtime::router::Router,
+    /// };
+    /// let mut router = Router::new();
+    /// router.route_method(Method::Get, "/users/:id", |req| async move {
+    ///     html_response(StatusCode::Ok, req.param("id").unwrap_or_default())
+    /// });
+    /// ```
+    pub fn route_method<F, Fut>(&mut self, method: Method, pattern: &str, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.insert_route(Some(method), pattern, None, handler);
+    }
+
+    /// Registers a new route for the router that only accepts bodies up to `max_body` bytes.
+    ///
+    /// Requests exceeding the limit are answered with `413 Content Too Large` without calling the handler. The server
+    /// resolves the limit once the headers are parsed, so such a body is never read and `Expect: 100-continue` is refused.
+    pub fn route_with_limit<F, Fut>(&mut self, path: &str, max_body: usize, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.insert_route(None, path, Some(max_body), handler);
+    }
+
+    /// Registers a handler taking over the connection of WebSocket opening handshakes for the passed pattern.
+    ///
+    /// The server answers valid handshakes with `101 Switching Protocols`, see `websocket::accept`, and passes the request
+    /// along with the connection to the handler. Reading and writing frames is left to the handler, the connection is
+    /// closed once it returns. Invalid handshakes are answered with `400 Bad Request`. Patterns work like the ones of `route_method`.
+    ///
+    /// # Examples
+    /// ```
+    /// use httpserver::runtime::router::Router;
+    /// use tokio::io::AsyncWriteExt;
+    /// let mut router = Router::new();
+    /// router.websocket("/chat/:room", |_req, mut connection| async move {
+    ///     let _ = connection.write_all(&[0x81, 0x02, b'h', b'i']).await;
+    ///     let _ = connection.flush().await;
+    /// });
+    /// ```
+    pub fn websocket<F, Fut>(&mut self, pattern: &str, handler: F)
+    where
+        F: Fn(Request, Upgraded) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let segments = parse_pattern(pattern);
+        self.websockets
+            .retain(|existing| existing.segments != segments);
+        self.websockets.push(WebSocketRoute {
+            segments,
+            handler: Box::new(move |req, connection| Box::pin(handler(req, connection))),
+        });
+    }
+
+    /// Whether a WebSocket handler takes over connections for the passed path.
     #[must_use]
-    fn retrieve(&self, endpoint: &str) -> Option<&HandlerFn> {
-        self.0.get(endpoint)
+    pub fn upgrades(&self, path: &str) -> bool {
+        self.find_websocket(path).is_some()
+    }
+
+    /// Hands the connection to the WebSocket handler registered for the path of the request, see `websocket`.
+    ///
+    /// Of several matching patterns the most specific one is chosen, like for `route_method`. The connection is dropped
+    /// without calling a handler if none matches.
+    pub async fn upgrade(&self, mut request: Request, connection: Upgraded) {
+        let Some((route, params)) = self.find_websocket(request.request_line.path()) else {
+            return;
+        };
+        request.path_params = params;
+        (route.handler)(request, connection).await;
+    }
+
+    /// Registers the handler called for requests no route matches, replacing the default `404 Not Found` response.
+    pub fn fallback<F, Fut>(&mut self, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.fallback = Some(Box::new(move |req| Box::pin(handler(req))));
+    }
+
+    /// Opts into tunneling for `CONNECT` requests whose `host:port` authority the passed filter accepts.
+    ///
+    /// The server answers accepted requests with `200 Connection Established` and relays bytes between the client and a TCP
+    /// connection to the authority until either side closes. Other `CONNECT` requests are routed like any request.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut router = httpserver::runtime::router::Router::new();
+    /// router.allow_connect(|authority| authority.ends_with(":443"));
+    /// assert!(router.allows_tunnel("example.com:443"));
+    /// assert!(!router.allows_tunnel("example.com:25"));
+    /// ```
+    pub fn allow_connect<F>(&mut self, filter: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.tunnel = Some(Box::new(filter));
+    }
+
+    /// Whether a `CONNECT` request to the passed authority opens a tunnel instead of being routed.
+    #[must_use]
+    pub fn allows_tunnel(&self, authority: &str) -> bool {
+        self.tunnel.as_ref().is_some_and(|filter| filter(authority))
+    }
+
+    /// Registers a callback observing every request the server answers, replacing a previously registered one.
+    ///
+    /// The callback receives the request line, the status of the response and the time since the request was parsed.
+    /// It runs on the connection before the response is written, so slow work such as writing to a file should be handed off.
+    ///
+    /// Requests rejected while parsing, such as with `413 Content Too Large`, are reported as soon as their request line was
+    /// parsed, with the time since then. Requests whose request line could not be parsed are not reported.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut router = httpserver::runtime::router::Router::new();
+    /// router.on_request(|request_line, status, duration| {
+    ///     println!("{} {} {status} {duration:?}", request_line.method, request_line.request_target);
+    /// });
+    /// assert!(router.observes_requests());
+    /// ```
+    pub fn on_request<F>(&mut self, hook: F)
+    where
+        F: Fn(&RequestLine, StatusCode, Duration) + Send + Sync + 'static,
+    {
+        self.request_hook = Some(Box::new(hook));
+    }
+
+    /// Whether a callback observing requests is registered.
+    #[must_use]
+    pub const fn observes_requests(&self) -> bool {
+        self.request_hook.is_some()
+    }
+
+    /// Runs the callback observing requests, if one is registered.
+    pub fn request_completed(
+        &self,
+        request_line: &RequestLine,
+        status: StatusCode,
+        duration: Duration,
+    ) {
+        if let Some(hook) = &self.request_hook {
+            hook(request_line, status, duration);
+        }
+    }
+
+    /// Adds a route, replacing a previously registered one with the same method and pattern.
+    fn insert_route<F, Fut>(
+        &mut self,
+        method: Option<Method>,
+        pattern: &str,
+        max_body: Option<usize>,
+        handler: F,
+    ) where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        let route = Route {
+            method,
+            segments: parse_pattern(pattern),
+            handler: Box::new(move |req| Box::pin(handler(req))),
+            max_body,
+        };
+        self.routes
+            .retain(|existing| existing.method != method || existing.segments != route.segments);
+        self.routes.push(route);
+    }
+
+    /// Returns the body size limit of the route the passed request line is dispatched to, see `route_with_limit`.
+    ///
+    /// The server resolves it once the headers are parsed, so a body exceeding it is rejected before it is read.
+    #[must_use]
+    pub fn body_limit(&self, request_line: &RequestLine) -> Option<usize> {
+        self.find_route(request_line.method, request_line.path())
+            .and_then(|(route, _)| route.max_body)
     }
 
     /// Determines what happens to a given request.
     ///
+    /// Routes are matched against the path of the request target, ignoring the query string.
+    ///
     /// # Errors
     /// Throws an `HttpError` if processing the request fails.
-    pub async fn call(&self, request: Request) -> Result<Response, HttpError> {
-        let endpoint = request.request_line.request_target.as_str();
-        let closure: Option<&HandlerFn> = self.retrieve(endpoint);
-        let response = if let Some(closure) = closure {
-            let result = closure(request);
-            result.await
-        } else {
+    pub async fn call(&self, mut request: Request) -> Result<Response, HttpError> {
+        let method = request.request_line.method;
+        let Some((route, params)) = self.find_route(method, request.request_line.path()) else {
+            let allowed = self.allowed_methods(request.request_line.path());
+            if !allowed.is_empty() {
+                let body = "<html><body><h1>Method Not Allowed</h1></body></html>";
+                let mut response = html_response(StatusCode::MethodNotAllowed, body);
+                let mut names: Vec<&str> = Vec::new();
+                for name in allowed.iter().map(Method::as_str) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                response.headers.insert("allow", names.join(", "));
+                return Ok(response);
+            }
+            if let Some(fallback) = &self.fallback {
+                return Ok(fallback(request).await);
+            }
             let body = "<html><body><h1>Not Found</h1></body></html>";
-            html_response(StatusCode::NotFound, body)
+            return Ok(html_response(StatusCode::NotFound, body));
         };
-        Ok(response)
+
+        if route
+            .max_body
+            .is_some_and(|max_body| request.body_len() > max_body)
+        {
+            let body = "<html><body><h1>Content Too Large</h1></body></html>";
+            return Ok(html_response(StatusCode::ContentTooLarge, body));
+        }
+        request.path_params = params;
+        Ok((route.handler)(request).await)
+    }
+}
+
+/// Matches the passed path against the segments of a pattern, returning the extracted parameters percent-decoded.
+///
+/// Returns None if the path does not match or a parameter is not validly percent-encoded.
+fn match_segments(segments: &[Segment], path: &str) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    let mut parts = path.split('/');
+    for segment in segments {
+        let part = parts.next()?;
+        match segment {
+            Segment::Static(expected) if expected == part => {}
+            Segment::Param(name) if !part.is_empty() => {
+                params.insert(name.clone(), percent_decode_path(part)?);
+            }
+            _ => return None,
+        }
+    }
+    parts.next().is_none().then_some(params)
+}
+
+impl Router {
+    /// Finds the route dispatching requests with the passed method and path, along with the extracted parameters.
+    ///
+    /// Of several matching routes, the one with a static segment where the others have a parameter wins, comparing the
+    /// segments from left to right. Routes that are equally specific are chosen in registration order.
+    fn find_route(&self, method: Method, path: &str) -> Option<(&Route, HashMap<String, String>)> {
+        let mut best: Option<(&Route, HashMap<String, String>)> = None;
+        for route in self.routes.iter().filter(|route| route.accepts(method)) {
+            let Some(params) = route.matches(path) else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(best_route, _)| {
+                more_specific_than(&route.segments, &best_route.segments)
+            }) {
+                best = Some((route, params));
+            }
+        }
+        best
+    }
+
+    /// Finds the WebSocket route for the passed path along with the extracted parameters, chosen like in `find_route`.
+    fn find_websocket(&self, path: &str) -> Option<(&WebSocketRoute, HashMap<String, String>)> {
+        let mut best: Option<(&WebSocketRoute, HashMap<String, String>)> = None;
+        for route in &self.websockets {
+            let Some(params) = match_segments(&route.segments, path) else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(best_route, _)| {
+                more_specific_than(&route.segments, &best_route.segments)
+            }) {
+                best = Some((route, params));
+            }
+        }
+        best
+    }
+
+    /// Returns the methods of the routes matching the passed path, `HEAD` following `GET`.
+    fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            if let Some(method) = route.method
+                && route.matches(path).is_some()
+            {
+                allowed.push(method);
+                if method == Method::Get {
+                    allowed.push(Method::Head);
+                }
+            }
+        }
+        allowed
+    }
+}
+
+impl Route {
+    /// Whether the route accepts the passed method, routes for `GET` answering `HEAD` requests as well.
+    fn accepts(&self, method: Method) -> bool {
+        self.method.is_none_or(|route_method| {
+            route_method == method || (route_method == Method::Get && method == Method::Head)
+        })
+    }
+
+    /// Matches the passed path against the pattern of the route, returning the extracted parameters.
+    ///
+    /// Returns None if the path does not match.
+    fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        match_segments(&self.segments, path)
     }
 }
 
+/// Whether the first segment in which both patterns differ in kind is static in the pattern of `segments`.
+fn more_specific_than(segments: &[Segment], other: &[Segment]) -> bool {
+    let is_static = |segment: &Segment| matches!(segment, Segment::Static(_));
+    segments
+        .iter()
+        .map(is_static)
+        .gt(other.iter().map(is_static))
+}
+
+/// Splits a route pattern into its segments.
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    pattern
+        .split('/')
+        .map(|part| {
+            part.strip_prefix(':').map_or_else(
+                || Segment::Static(part.to_string()),
+                |name| Segment::Param(name.to_string()),
+            )
+        })
+        .collect()
+}
+
 impl Default for Router {
     fn default() -> Self {
         Self::new()
@@ -67,3 +449,219 @@ impl std::fmt::Debug for Router {
         f.write_str("<closure>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+    use crate::{
+        http::{
+            request::{Request, request_from_reader},
+            request_line::Method,
+            response::{StatusCode, html_response},
+            websocket::Upgraded,
+        },
+        runtime::{router::Router, server::Settings},
+    };
+
+    async fn post_request(path: &str, body: &str) -> Request {
+        let input = format!(
+            "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+        request_from_reader(&mut input.as_bytes(), &settings)
+            .await
+            .unwrap()
+    }
+
+    async fn get_request(target: &str) -> Request {
+        let input = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        let settings: Settings = config.try_deserialize().unwrap();
+        request_from_reader(&mut input.as_bytes(), &settings)
+            .await
+            .unwrap()
+    }
+
+    fn user_router() -> Router {
+        let mut router = Router::new();
+        router.route_method(Method::Get, "/users/me", |_req| async {
+            html_response(StatusCode::Ok, "me")
+        });
+        router.route_method(Method::Get, "/users/:id", |req| async move {
+            html_response(StatusCode::Ok, req.param("id").unwrap_or_default())
+        });
+        router.route_method(Method::Post, "/users", |_req| async {
+            html_response(StatusCode::Created, "created")
+        });
+        router
+    }
+
+    #[tokio::test]
+    async fn static_segments_match_exactly_and_take_precedence() {
+        let router = user_router();
+
+        let me = router.call(get_request("/users/me").await).await.unwrap();
+        let created = router
+            .call(post_request("/users", "{}").await)
+            .await
+            .unwrap();
+
+        assert_eq!(me.body, b"me");
+        assert_eq!(created.status, StatusCode::Created);
+    }
+
+    #[tokio::test]
+    async fn param_segments_are_extracted() {
+        let router = user_router();
+
+        let response = router
+            .call(get_request("/users/42?verbose=true").await)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.body, b"42");
+    }
+
+    #[tokio::test]
+    async fn earlier_static_segment_takes_precedence_over_fewer_params() {
+        let mut router = Router::new();
+        router.route_method(Method::Get, "/:org/repos/settings", |_req| async {
+            html_response(StatusCode::Ok, "param first")
+        });
+        router.route_method(Method::Get, "/users/:id/:tab", |_req| async {
+            html_response(StatusCode::Ok, "static first")
+        });
+
+        let response = router
+            .call(get_request("/users/repos/settings").await)
+            .await
+            .unwrap();
+
+        assert_eq!(response.body, b"static first");
+    }
+
+    #[tokio::test]
+    async fn static_websocket_pattern_takes_precedence_over_param() {
+        let mut router = Router::new();
+        router.websocket("/chat/:room", |_req, mut connection| async move {
+            connection.write_all(b"room").await.unwrap();
+        });
+        router.websocket("/chat/admin", |_req, mut connection| async move {
+            connection.write_all(b"admin").await.unwrap();
+        });
+        let (server, mut client) = duplex(64);
+
+        router
+            .upgrade(
+                get_request("/chat/admin").await,
+                Upgraded::new(server, Vec::new()),
+            )
+            .await;
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+
+        assert!(router.upgrades("/chat/lobby"));
+        assert_eq!(received, b"admin");
+    }
+
+    #[tokio::test]
+    async fn param_values_are_percent_decoded() {
+        let router = user_router();
+
+        let decoded = router
+            .call(get_request("/users/jane%20doe").await)
+            .await
+            .unwrap();
+        let invalid = router.call(get_request("/users/%zz").await).await.unwrap();
+
+        assert_eq!(decoded.body, b"jane doe");
+        assert_eq!(invalid.status, StatusCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn unmatched_requests_fall_through() {
+        let mut router = user_router();
+
+        let not_found = router
+            .call(get_request("/users/42/posts").await)
+            .await
+            .unwrap();
+        let wrong_method = router
+            .call(post_request("/users/42", "{}").await)
+            .await
+            .unwrap();
+        assert_eq!(not_found.status, StatusCode::NotFound);
+        assert_eq!(wrong_method.status, StatusCode::MethodNotAllowed);
+        assert_eq!(wrong_method.headers.get("allow"), Some("GET, HEAD"));
+
+        router.fallback(|_req| async { html_response(StatusCode::Ok, "fallback") });
+        let fallback = router.call(get_request("/missing").await).await.unwrap();
+        assert_eq!(fallback.body, b"fallback");
+    }
+
+    #[tokio::test]
+    async fn get_routes_answer_head_requests() {
+        let router = user_router();
+        let input = "HEAD /users/42 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::from_slice(input.as_bytes()).unwrap();
+
+        let response = router.call(request).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.body, b"42");
+    }
+
+    #[tokio::test]
+    async fn closure_handler_serves_ok() {
+        let greeting = String::from("Hello");
+        let mut router = Router::new();
+        router.route("/greet", move |req| {
+            let body = format!("{greeting}, {} bytes", req.body.len());
+            async move { html_response(StatusCode::Ok, &body) }
+        });
+
+        let response = router
+            .call(post_request("/greet", "world").await)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.body, b"Hello, 5 bytes");
+    }
+
+    #[tokio::test]
+    async fn routes_enforce_their_own_body_limit() {
+        let mut router = Router::new();
+        router.route_with_limit("/json", 8, |_req| async {
+            html_response(StatusCode::Ok, "json")
+        });
+        router.route_with_limit("/upload", 64, |_req| async {
+            html_response(StatusCode::Ok, "upload")
+        });
+        let body = "x".repeat(32);
+
+        let json_small = router
+            .call(post_request("/json", "{}").await)
+            .await
+            .unwrap();
+        let json_large = router
+            .call(post_request("/json", &body).await)
+            .await
+            .unwrap();
+        let upload = router
+            .call(post_request("/upload", &body).await)
+            .await
+            .unwrap();
+
+        assert!(matches!(json_small.status, StatusCode::Ok));
+        assert!(matches!(json_large.status, StatusCode::ContentTooLarge));
+        assert!(matches!(upload.status, StatusCode::Ok));
+    }
+}
@@ -1,11 +1,25 @@
-use crate::http::response::{write_headers, write_status_line};
 use crate::http::{
     headers::Headers,
-    request::{HttpError, request_from_reader},
-    response::{Response, StatusCode, html_response},
+    limits::ParseLimits,
+    request::{HttpError, Request, request_from_connection, request_from_reader},
+    request_line::{Method, RequestLine},
+    response::{
+        Response, StatusCode, compress_response, html_response, write_head_response_without_flush,
+        write_response, write_response_without_flush,
+    },
+    websocket::{self, Upgraded},
+};
+use crate::runtime::{
+    context::RequestContext,
+    error_pages::ErrorPages,
+    inflight::InflightLimiter,
+    log::{AccessLog, ErrorLog, next_request_id},
+    memory::MemoryBudget,
+    redirect::RedirectToHttps,
+    router::Router,
 };
-use crate::runtime::router::Router;
 use config::{Config, ConfigError, File};
+use futures::FutureExt;
 use rustls::{
     ServerConfig,
     pki_types::{CertificateDer, PrivatePkcs8KeyDer, pem::PemObject},
@@ -13,39 +27,91 @@ use rustls::{
 use serde::Deserialize;
 use std::{
     collections::HashMap,
-    fmt::Debug,
+    fmt::{Debug, Display},
     io::Error,
-    net::IpAddr,
+    net::{IpAddr, SocketAddr},
+    panic::AssertUnwindSafe,
     sync::{
         Arc, Mutex,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     time::Duration,
 };
 use tokio::{
-    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
-    net::TcpListener,
-    sync::Semaphore,
-    time::{sleep, timeout},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, copy_bidirectional},
+    net::{TcpListener, TcpStream},
+    sync::{Notify, Semaphore},
+    task::{JoinHandle, JoinSet},
+    time::{Instant, sleep, timeout},
 };
-use tokio_rustls::TlsAcceptor;
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
 
 /// A struct representing an instance of a `HttpServer`, containing the state of the server.
 #[derive(Debug)]
 pub struct Server {
     server_state: Arc<ServerState>,
+    listen_task: JoinHandle<()>,
 }
 
+/// A custom type boxing a callback run once during shutdown.
+type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+/// The callbacks `Server::shutdown` runs once the connections are drained, in registration order.
+#[derive(Default)]
+struct ShutdownHooks(Mutex<Vec<ShutdownHook>>);
+
 /// A struct representing the state of a server with the associated listener, whether or not the server has been closed and the handler.
 #[derive(Debug)]
 struct ServerState {
     listener: TcpListener,
     http_listener: TcpListener,
     closed: AtomicBool,
+    shutdown: Notify,
     limiter: ConnectionLimiter,
     tls_config: Arc<ServerConfig>,
     router: Arc<Router>,
     settings: Arc<Settings>,
+    shared: SharedState,
+    shutdown_hooks: ShutdownHooks,
+    active_connections: Arc<AtomicUsize>,
+}
+
+/// The state shared by all connections of a server: its memory and in-flight limits, the error pages loaded at startup,
+/// the logs and whether the server is shutting down.
+#[derive(Clone, Debug)]
+struct SharedState {
+    /// The budget for buffered request bodies
+    memory: MemoryBudget,
+    /// The limit on requests handled at the same time
+    inflight: InflightLimiter,
+    /// The custom pages of error responses
+    error_pages: ErrorPages,
+    /// The log handled requests are recorded in, None if disabled
+    access_log: Option<AccessLog>,
+    /// The log the errors behind error responses are recorded in
+    error_log: ErrorLog,
+    /// Set once the server starts shutting down, so connections close after their in-flight response
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl SharedState {
+    /// Creates the shared state configured in the passed settings, reading the configured error pages.
+    fn new(settings: &Settings) -> Self {
+        Self {
+            memory: MemoryBudget::new(settings.global_body_limit_in_mib * 1024 * 1024),
+            inflight: InflightLimiter::new(
+                settings.max_inflight_requests,
+                Duration::from_secs(settings.inflight_queue_timeout),
+            ),
+            error_pages: ErrorPages::load(&settings.error_pages),
+            access_log: match settings.access_log {
+                AccessLogTarget::Off => None,
+                AccessLogTarget::Stdout => Some(AccessLog::new(std::io::stdout())),
+            },
+            error_log: ErrorLog::new(std::io::stderr()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+        }
+    }
 }
 
 /// A struct containing the configurable parts of the application
@@ -55,28 +121,191 @@ pub struct Settings {
     port: u16,
     /// The port to be used to listen for http requests, which are redirected to https
     http_port: u16,
+    /// The https port that requests arriving on `http_port` are redirected to
+    https_redirect_port: u16,
     /// The total amount of clients able to connect to the server
     max_clients: usize,
     /// The directory in which the certificate private key is stored
     cert_key_dir: String,
     /// The directory in which the private key is stored
     tls_key_dir: String,
+    /// The application protocols offered via ALPN during the TLS handshake, in order of preference
+    alpn_protocols: Vec<String>,
     /// The address for the tcp listener
     tcp_listener_address: String,
     /// The connection limit per ip
     ip_connection_limit: usize,
     /// The timeout for processing a request
     connection_timeout: u64,
+    /// The timeout for connections to finish during a graceful shutdown before they are aborted
+    shutdown_drain_timeout: u64,
+    /// The maximum lifetime of a `CONNECT` tunnel, after which both sides are closed
+    tunnel_timeout: u64,
     /// The timeout for `keep_alive`
     keep_alive_timeout: u64,
     /// The timeout for parsing a request
     pub parsing_timeout: u64,
+    /// The timeout for receiving the request line and all headers, starting with the first byte of the request
+    pub header_timeout: u64,
     /// The size limit in `MIB` for the entire request
     pub request_size_limit_in_mib: usize,
+    /// The size limit in `MIB` for the bodies of all requests buffered at the same time
+    global_body_limit_in_mib: usize,
+    /// The amount of `MIB` of a body kept in memory before the rest is written to a temporary file, 0 keeps entire bodies in memory
+    pub body_spill_threshold_in_mib: usize,
+    /// The maximum amount of `KIB` requested from a connection per read while parsing a request
+    pub read_buffer_size_in_kib: usize,
+    /// The amount of requests handled at the same time across all connections
+    max_inflight_requests: usize,
+    /// The timeout for a request waiting for one of the `max_inflight_requests` slots, 0 rejects it immediately with `503 Service Unavailable`
+    inflight_queue_timeout: u64,
     /// The size limit in `KIB` for the entire request
     pub header_size_limit_in_kib: usize,
     /// The maximum amount of headers allowed per request
     pub max_header_size: usize,
+    /// The size limit in `KIB` for a single chunk of a chunked request body
+    pub max_chunk_size_in_kib: usize,
+    /// The amount of requests served on a single keep-alive connection before it is closed
+    max_keepalive_requests: usize,
+    /// Whether responses on keep-alive connections advertise the timeout and remaining requests in a `Keep-Alive` header
+    keep_alive_header: bool,
+    /// Whether responses report the handler duration in a `Server-Timing` header
+    server_timing_header: bool,
+    /// When responses are flushed to the connection
+    flush_strategy: FlushStrategy,
+    /// How requests asking to switch protocols via an `Upgrade` header are handled
+    upgrade_policy: UpgradePolicy,
+    /// Headers removed from every request before it reaches the handler, such as ones only a trusted proxy may set
+    strip_request_headers: Vec<String>,
+    /// Whether the server runs behind a proxy whose `X-Forwarded-Proto` header determines the scheme of requests
+    trust_forwarded_proto: bool,
+    /// When response bodies are compressed
+    pub compression: CompressionSettings,
+    /// Files served instead of the built-in pages of error responses the server generates, by status code
+    pub error_pages: HashMap<String, String>,
+    /// Where handled requests are logged
+    access_log: AccessLogTarget,
+}
+
+/// The configurable parts of response compression, read from the `[compression]` table.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub struct CompressionSettings {
+    /// Whether response bodies are compressed for clients accepting gzip or deflate, see `compress_response`
+    pub enabled: bool,
+    /// The size in bytes below which response bodies are sent uncompressed, see `Response::is_compressible`
+    pub min_size: usize,
+}
+
+impl Settings {
+    /// Checks the settings for contradicting values, which would otherwise only show as requests being rejected at runtime.
+    ///
+    /// # Errors
+    ///
+    /// Throws a `ConfigError::Message` naming the conflicting settings.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let request_size_limit_in_kib = self.request_size_limit_in_mib * 1024;
+        if self.port == self.http_port && self.port != 0 {
+            return Err(ConfigError::Message(format!(
+                "port and http_port are both set to {}",
+                self.port
+            )));
+        }
+        if self.max_chunk_size_in_kib > request_size_limit_in_kib {
+            return Err(ConfigError::Message(
+                "max_chunk_size_in_kib exceeds request_size_limit_in_mib, so a chunk of that size is never accepted"
+                    .to_string(),
+            ));
+        }
+        if self.header_size_limit_in_kib > request_size_limit_in_kib {
+            return Err(ConfigError::Message(
+                "header_size_limit_in_kib exceeds request_size_limit_in_mib".to_string(),
+            ));
+        }
+        if self.request_size_limit_in_mib > self.global_body_limit_in_mib {
+            return Err(ConfigError::Message(
+                "request_size_limit_in_mib exceeds global_body_limit_in_mib, so a single request cannot use its limit"
+                    .to_string(),
+            ));
+        }
+        if self.max_keepalive_requests == 0 {
+            return Err(ConfigError::Message(
+                "max_keepalive_requests has to allow at least one request".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the limits the request parser applies according to these settings.
+    #[must_use]
+    pub const fn parse_limits(&self) -> ParseLimits {
+        ParseLimits {
+            max_request_size: self.request_size_limit_in_mib * 1024 * 1024,
+            max_header_size: self.header_size_limit_in_kib * 1024,
+            max_header_count: self.max_header_size,
+            max_chunk_size: self.max_chunk_size_in_kib * 1024,
+            read_timeout: Duration::from_secs(self.parsing_timeout),
+            header_timeout: Duration::from_secs(self.header_timeout),
+            body_spill_threshold: if self.body_spill_threshold_in_mib == 0 {
+                None
+            } else {
+                Some(self.body_spill_threshold_in_mib * 1024 * 1024)
+            },
+            read_buffer_size: self.read_buffer_size_in_kib * 1024,
+        }
+    }
+}
+
+/// Determines when responses are flushed to the connection.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlushStrategy {
+    /// Flush after every response.
+    #[default]
+    Immediate,
+    /// Hold back responses while further pipelined requests are already received, flushing them together.
+    Coalesce,
+}
+
+/// Determines how requests containing an `Upgrade` header are handled, as the server never switches protocols.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpgradePolicy {
+    /// Pass the request to the handler and answer it as HTTP/1.1, keeping the connection usable.
+    #[default]
+    Ignore,
+    /// Answer with `501 Not Implemented` without calling the handler.
+    Reject,
+}
+
+/// Determines where handled requests are logged.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogTarget {
+    /// Requests are not logged.
+    #[default]
+    Off,
+    /// Every request is logged as a line on stdout.
+    Stdout,
+}
+
+/// Determines what happens to a connection once a request was processed.
+#[derive(Debug)]
+enum ConnectionState {
+    /// The connection stays open for the next request.
+    KeepOpen,
+    /// The connection is closed.
+    Close,
+    /// The connection relays bytes to the passed upstream connection of a `CONNECT` tunnel until either side closes.
+    Tunnel(TcpStream),
+    /// The connection is handed to the WebSocket handler for the passed request, whose handshake was answered.
+    WebSocket(Box<Request>),
+}
+
+impl Debug for ShutdownHooks {
+    /// Prints a placeholder for the hooks, as print debugging a closure is not feasible.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<closure>")
+    }
 }
 
 /// Limits connections for a certain Tcp Connection.
@@ -88,6 +317,23 @@ struct ConnectionLimiter {
     max_per_ip: usize,
 }
 
+/// RAII guard counting a connection as active until it is dropped.
+struct ActiveConnection(Arc<AtomicUsize>);
+
+impl ActiveConnection {
+    /// Counts a new connection in the passed counter.
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// RAII guard for each connection to be able to be dropped safely.
 struct ConnectionGuard {
     limiter: ConnectionLimiter,
@@ -95,54 +341,115 @@ struct ConnectionGuard {
 }
 
 impl Server {
-    /// Sets the closed state of the server it's called on.
+    /// Sets the closed state of the server it's called on, so it stops accepting connections.
+    ///
+    /// Connections already being handled continue in the background, see `shutdown` to wait for them. They are closed
+    /// after their in-flight response, which carries `Connection: close`, instead of reading another request.
     pub fn close(&self) {
         self.server_state.closed.store(true, Ordering::SeqCst);
+        self.server_state
+            .shared
+            .shutting_down
+            .store(true, Ordering::SeqCst);
+        self.server_state.shutdown.notify_one();
+    }
+
+    /// Stops accepting connections and waits for the ones being handled to finish.
+    ///
+    /// Connections still active after `shutdown_drain_timeout` seconds are aborted. The listeners are released and the
+    /// hooks registered with `on_shutdown` are run before this returns.
+    pub async fn shutdown(self) {
+        self.close();
+        if let Err(e) = self.listen_task.await {
+            eprintln!("Encountered error stopping the listener: {e}");
+        }
+        let hooks = match self.server_state.shutdown_hooks.0.lock() {
+            Ok(mut hooks) => std::mem::take(&mut *hooks),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        };
+        for hook in hooks {
+            hook();
+        }
+    }
+
+    /// Registers a callback that `shutdown` runs after the connections are drained, such as flushing metrics.
+    ///
+    /// Several hooks are run in registration order. Hooks are not run if the server is dropped without calling `shutdown`.
+    pub fn on_shutdown(&self, hook: impl FnOnce() + Send + 'static) {
+        let mut hooks = match self.server_state.shutdown_hooks.0.lock() {
+            Ok(hooks) => hooks,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        hooks.push(Box::new(hook));
+    }
+
+    /// Returns the amount of connections currently open on both listeners.
+    ///
+    /// A connection is counted from being accepted until it is closed, including ones idling between keep-alive requests.
+    #[must_use]
+    pub fn active_connections(&self) -> usize {
+        self.server_state.active_connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns the address the HTTPS listener is bound to.
+    ///
+    /// Reports the port assigned by the operating system if `port` was configured as 0.
+    ///
+    /// # Errors
+    ///
+    /// Throws an Error if the address of the listener cannot be determined.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.server_state.listener.local_addr()
+    }
+
+    /// Returns the address the plain HTTP listener redirecting to HTTPS is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Throws an Error if the address of the listener cannot be determined.
+    pub fn http_local_addr(&self) -> Result<SocketAddr, Error> {
+        self.server_state.http_listener.local_addr()
     }
 }
 
 impl ServerState {
     /// Called on a `ServerState`, listening for connections.
+    ///
+    /// A connection is only accepted once one of the `max_clients` permits is free, so further clients wait in the
+    /// backlog of the listener until a running connection finishes.
     pub async fn listen(self: Arc<Self>) {
         let max_clients = self.settings.max_clients;
         let sem = Arc::new(Semaphore::new(max_clients));
         let acceptor = Arc::new(TlsAcceptor::from(Arc::clone(&self.tls_config)));
+        let mut connections = JoinSet::new();
         loop {
             if self.closed.load(Ordering::SeqCst) {
                 println!("We cannot take any new connections as the server was closed.");
-                return;
+                break;
             }
+            let global_guard = tokio::select! {
+                () = self.shutdown.notified() => continue,
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                Ok(permit) = Arc::clone(&sem).acquire_owned() => permit,
+            };
             tokio::select! {
+                () = self.shutdown.notified() => {}
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
                 result = self.listener.accept() => {
                     match result {
                         Ok((mut stream, addr)) => {
+                            let active = ActiveConnection::new(&self.active_connections);
                             let ip = addr.ip();
                             if let Some(ip_guard) = self.limiter.try_connect(ip) {
                                 let router_clone = Arc::clone(&self.router);
-                                let sem_clone = Arc::clone(&sem);
                                 let acceptor_clone = Arc::clone(&acceptor);
                                 let settings_clone = Arc::clone(&self.settings);
-                                tokio::spawn(async move {
-                                    if let Ok(global_guard) = sem_clone.try_acquire() {
-                                        println!("Accepted a new connection");
-                                        let _guard = ip_guard; //move ownership
-                                        let _global_guard = global_guard; //move ownership
-                                        match TlsAcceptor::accept(&acceptor_clone, &mut stream).await {
-                                            Ok(tls_stream) => {
-                                                if let Err(e) =
-                                                    handle(tls_stream, &router_clone, &settings_clone).await
-                                                {
-                                                    eprintln!("Encountered error handling the stream: {e}");
-                                                }
-                                            }
-                                            Err(err) => {
-                                                eprintln!("Encountered error during TSL handshake: {err}");
-                                            }
-                                        }
-                                    } else {
-                                        println!("Too many connections, rejecting client.");
-                                        let _ = stream.shutdown().await;
-                                    }
+                                let shared_clone = self.shared.clone();
+                                connections.spawn(async move {
+                                    let _active = active;
+                                    let _guard = ip_guard; //move ownership
+                                    let _global_guard = global_guard; //move ownership
+                                    serve_tls_connection(stream, &acceptor_clone, &router_clone, &settings_clone, &shared_clone).await;
                                 });
                             } else {
                                 println!("Shutting down, request limit reached");
@@ -161,19 +468,16 @@ impl ServerState {
                 result = self.http_listener.accept() => {
                     match result {
                         Ok((mut stream, addr)) => {
+                            let active = ActiveConnection::new(&self.active_connections);
                             let ip = addr.ip();
                             if let Some(ip_guard) = self.limiter.try_connect(ip) {
-                                let sem_clone = Arc::clone(&sem);
                                 let settings_clone = Arc::clone(&self.settings);
-                                tokio::spawn(async move {
-                                    if let Ok(global_guard) = sem_clone.try_acquire() {
-                                        let _guard = ip_guard; //move ownership
-                                        let _global_guard = global_guard; //move ownership
-                                        let _ = handle_redirect(stream, &settings_clone).await;
-                                    } else {
-                                        println!("Too many connections, rejecting client.");
-                                        let _ = stream.shutdown().await;
-                                    }
+                                let shared_clone = self.shared.clone();
+                                connections.spawn(async move {
+                                    let _active = active;
+                                    let _guard = ip_guard; //move ownership
+                                    let _global_guard = global_guard; //move ownership
+                                    let _ = handle_redirect(stream, &settings_clone, &shared_clone).await;
                                 });
                             } else {
                                 println!("Shutting down, request limit reached");
@@ -187,9 +491,66 @@ impl ServerState {
                 }
             }
         }
+        self.drain(connections).await;
+    }
+
+    /// Waits for the connections still being handled to finish, aborting the ones active after the drain timeout.
+    async fn drain(&self, mut connections: JoinSet<()>) {
+        let drain_timeout = Duration::from_secs(self.settings.shutdown_drain_timeout);
+        let drained = timeout(drain_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            println!(
+                "Aborting {} connections still active after the drain timeout.",
+                connections.len()
+            );
+            connections.shutdown().await;
+        }
+    }
+}
+
+/// Serves a connection accepted on the HTTPS listener, performing the TLS handshake before handling its requests.
+async fn serve_tls_connection(
+    mut stream: TcpStream,
+    acceptor: &TlsAcceptor,
+    router: &Router,
+    settings: &Settings,
+    shared: &SharedState,
+) {
+    if is_plaintext_http(&stream).await {
+        println!("Received plaintext HTTP on the TLS port, rejecting client.");
+        let _ = reject_plaintext_http(&mut stream).await;
+        return;
+    }
+    let result = match accept_tls(acceptor, stream).await {
+        Ok(tls_stream) => match connection_context(&tls_stream) {
+            Ok(context) => handle(tls_stream, router, settings, shared, &context).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        eprintln!("Encountered error handling the stream: {e}");
     }
 }
 
+/// Performs the TLS handshake on an accepted stream.
+///
+/// # Errors
+///
+/// Throws `HttpError::TlsHandshake` if the handshake fails, such as when the client does not speak TLS or aborts.
+async fn accept_tls<IO: AsyncRead + AsyncWrite + Unpin>(
+    acceptor: &TlsAcceptor,
+    stream: IO,
+) -> Result<TlsStream<IO>, HttpError> {
+    acceptor
+        .accept(stream)
+        .await
+        .map_err(HttpError::TlsHandshake)
+}
+
 impl ConnectionLimiter {
     fn new(max_per_ip: usize) -> Self {
         Self {
@@ -233,11 +594,13 @@ impl Drop for ConnectionGuard {
     }
 }
 
-/// Helper function to extract a TLS server config.
+/// Helper function to extract a TLS server config, offering the configured ALPN protocols.
+///
+/// Connections negotiating a protocol other than `http/1.1` are rejected after the handshake, see `connection_context`.
 ///
 /// # Errors
 ///
-/// Throws an Error if reading files fails.s
+/// Throws an Error if reading files fails.
 fn build_tls_config(settings: &Settings) -> Result<ServerConfig, Error> {
     let cert_dir = settings.cert_key_dir.clone();
     let pk_dir = settings.tls_key_dir.clone();
@@ -250,31 +613,101 @@ fn build_tls_config(settings: &Settings) -> Result<ServerConfig, Error> {
     let key_der = PrivatePkcs8KeyDer::from_pem_file(pk_dir)
         .map_err(Error::other)?
         .into();
-    let config = config_builder
+    let mut config = config_builder
         .with_single_cert(cert_chain, key_der)
         .map_err(Error::other)?;
+    config.alpn_protocols = settings
+        .alpn_protocols
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
 
     Ok(config)
 }
 
+/// Helper function to detect a client sending plaintext HTTP to the TLS port.
+///
+/// A TLS connection starts with a handshake record (`0x16`), whereas plaintext HTTP starts with the letters of a method.
+async fn is_plaintext_http(stream: &TcpStream) -> bool {
+    let mut first_byte = [0u8; 1];
+    matches!(stream.peek(&mut first_byte).await, Ok(1)) && first_byte[0].is_ascii_uppercase()
+}
+
+/// Answers a plaintext HTTP request on the TLS port with a `400 Bad Request` pointing to HTTPS and closes the connection.
+///
+/// The pending request data is consumed first, as closing a socket with unread data resets the connection before the client reads the response.
+///
+/// # Errors
+///
+/// Throws an `HttpError` if reading or writing the stream fails.
+async fn reject_plaintext_http(stream: &mut TcpStream) -> Result<(), HttpError> {
+    let mut pending = [0u8; 4096];
+    let _ = stream.read(&mut pending).await?;
+    write_response(&mut *stream, plaintext_on_tls_response()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Helper function building the plaintext response sent to clients that used HTTP on the TLS port.
+fn plaintext_on_tls_response() -> Response {
+    let body = "This port only accepts HTTPS. Please connect using https:// instead.";
+    let mut headers = Headers::new();
+    headers.insert("content-type", "text/plain");
+    headers.insert("content-length", body.len().to_string());
+    headers.insert("connection", "close");
+    Response {
+        status: StatusCode::BadRequest,
+        headers,
+        body: body.as_bytes().to_vec(),
+        channel: None,
+    }
+}
+
+/// Helper function to build the context of a connection from its completed TLS handshake.
+///
+/// # Errors
+///
+/// Throws an `HttpError::UnsupportedProtocol` if ALPN negotiated a protocol other than HTTP/1.1.
+fn connection_context<IO>(tls_stream: &TlsStream<IO>) -> Result<RequestContext, HttpError> {
+    let alpn_protocol = tls_stream
+        .get_ref()
+        .1
+        .alpn_protocol()
+        .map(|protocol| String::from_utf8_lossy(protocol).into_owned());
+
+    if let Some(protocol) = &alpn_protocol
+        && protocol != "http/1.1"
+    {
+        return Err(HttpError::UnsupportedProtocol(protocol.clone()));
+    }
+
+    Ok(RequestContext {
+        alpn_protocol,
+        is_secure: true,
+        ..RequestContext::default()
+    })
+}
+
 /// Serves an instance of the Http Server based on the passed handler on the specified port
 ///
+/// The listeners bind to `tcp_listener_address`, such as `0.0.0.0` for all interfaces. A port of 0 lets the operating system
+/// assign a free one, which `Server::local_addr` reports.
+///
 /// # Errors
 ///
-/// Throws an Error if binding the tcp listener fails.
+/// Throws an Error if the settings are invalid, see `Settings::validate`, or if binding the tcp listener fails.
 pub async fn serve(config: Config, router: Router) -> Result<Server, Error> {
     let settings = Arc::new(config.try_deserialize::<Settings>().map_err(Error::other)?);
+    settings.validate().map_err(Error::other)?;
     let router = Arc::new(router);
     let listener =
         TcpListener::bind((settings.tcp_listener_address.as_str(), settings.port)).await?;
     let http_listener =
         TcpListener::bind((settings.tcp_listener_address.as_str(), settings.http_port)).await?;
     let limiter = ConnectionLimiter::new(settings.ip_connection_limit);
+    let shared = SharedState::new(&settings);
 
-    let mut server_config = build_tls_config(&settings)?;
-    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
-
-    let tls_config = Arc::new(server_config);
+    let tls_config = Arc::new(build_tls_config(&settings)?);
     let state = ServerState {
         listener,
         http_listener,
@@ -282,17 +715,21 @@ pub async fn serve(config: Config, router: Router) -> Result<Server, Error> {
         limiter,
         tls_config,
         closed: AtomicBool::new(false),
+        shutdown: Notify::new(),
         settings,
+        shared,
+        shutdown_hooks: ShutdownHooks::default(),
+        active_connections: Arc::new(AtomicUsize::new(0)),
     };
     let state_for_main = Arc::new(state);
     let state_for_thread = Arc::clone(&state_for_main);
-    let server_handle = Server {
-        server_state: state_for_main,
-    };
-    tokio::spawn(async move {
+    let listen_task = tokio::spawn(async move {
         state_for_thread.listen().await;
     });
-    Ok(server_handle)
+    Ok(Server {
+        server_state: state_for_main,
+        listen_task,
+    })
 }
 
 /// Handles a specific connection's parsing based on the associated TCP stream.
@@ -300,39 +737,70 @@ pub async fn serve(config: Config, router: Router) -> Result<Server, Error> {
 /// # Errors
 ///
 /// Throws an `HttpError` if the parsing process fails.
-async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
-    mut stream: S,
+async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    stream: S,
     router: &Router,
     settings: &Settings,
+    shared: &SharedState,
+    context: &RequestContext,
 ) -> Result<(), HttpError> {
     let server_timeout_amount = settings.connection_timeout;
     let server_timeout = Duration::from_secs(server_timeout_amount);
+    let mut remaining_requests = settings.max_keepalive_requests;
+    let mut stream = BufWriter::new(stream);
+    let mut pending = Vec::new();
 
     loop {
+        if shared.shutting_down.load(Ordering::SeqCst) {
+            break;
+        }
         let result = timeout(
             server_timeout,
-            process_request(&mut stream, router, settings),
+            process_request(
+                &mut stream,
+                router,
+                settings,
+                shared,
+                context,
+                remaining_requests,
+                &mut pending,
+            ),
         )
         .await;
+        remaining_requests = remaining_requests.saturating_sub(1);
 
         match result {
-            Ok(Ok(should_continue)) => {
-                if !should_continue {
-                    return Ok(());
+            Ok(Ok(ConnectionState::KeepOpen)) => {}
+            Ok(Ok(ConnectionState::Close)) => return Ok(()),
+            // The tunnel outlives the request, so it is limited by its own timeout instead of the connection timeout.
+            Ok(Ok(ConnectionState::Tunnel(mut upstream))) => {
+                let tunnel_timeout = Duration::from_secs(settings.tunnel_timeout);
+                let relayed = timeout(tunnel_timeout, async {
+                    upstream.write_all(&pending).await?;
+                    copy_bidirectional(&mut stream, &mut upstream).await
+                })
+                .await;
+                if let Ok(result) = relayed {
+                    result?;
                 }
+                return Ok(());
+            }
+            // The handler owns the connection from here on, so it is not limited by the connection timeout either.
+            Ok(Ok(ConnectionState::WebSocket(request))) => {
+                let connection = Upgraded::new(stream.into_inner(), pending);
+                router.upgrade(*request, connection).await;
+                return Ok(());
             }
             Ok(Err(_e)) => {
                 break;
             }
             Err(_elapsed) => {
                 let html = "<html><body><h1>Gateway Timed out</h1></body></html>";
-                let response = html_response(StatusCode::GatewayTimeout, html);
+                let error = format!("connection timed out after {server_timeout_amount}s");
+                let response =
+                    logged_error_response(shared, StatusCode::GatewayTimeout, html, error);
 
-                write_status_line(&mut stream, response.status).await?;
-                let mut headers = response.headers;
-                write_headers(&mut stream, &mut headers).await?;
-                stream.write_all(&response.body).await?;
-                stream.flush().await?;
+                write_response(&mut stream, response).await?;
                 break;
             }
         }
@@ -342,12 +810,15 @@ async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
 /// Handles redirecting an HTTP Request to HTTPS.
 ///
+/// Requests that cannot be redirected are answered like rejected requests on the HTTPS listener, tagged with a request id.
+///
 /// # Errors
 ///
 /// Throws an `HttpError` if the parsing process fails.
 async fn handle_redirect<S: AsyncRead + AsyncWrite + Unpin + Send>(
     mut stream: S,
     settings: &Settings,
+    shared: &SharedState,
 ) -> Result<(), HttpError> {
     let server_timeout_amount = settings.connection_timeout;
     let server_timeout = Duration::from_secs(server_timeout_amount);
@@ -359,47 +830,27 @@ async fn handle_redirect<S: AsyncRead + AsyncWrite + Unpin + Send>(
         Ok(Err(HttpError::UnexpectedEOF)) => {
             return Ok(());
         }
-        Ok(Err(HttpError::Timeout)) => {
-            let html = "<html><body><h1>Request timed out</h1></body></html>";
-            let response = html_response(StatusCode::RequestTimeout, html);
-
-            write_response(&mut stream, response).await?;
-            return Ok(());
-        }
-        Ok(Err(_e)) => {
-            let html = "<html><body><h1>Bad Request</h1></body></html>";
-            let response = html_response(StatusCode::BadRequest, html);
-
+        Ok(Err(e)) => {
+            let response = rejected_request_response(&e, shared);
             write_response(&mut stream, response).await?;
             return Ok(());
         }
         Err(_) => {
-            let html = "<html><body><h1>Bad Request</h1></body></html>";
-            let response = html_response(StatusCode::BadRequest, html);
+            let response = rejected_request_response(&HttpError::Timeout, shared);
             write_response(&mut stream, response).await?;
             return Ok(());
         }
     };
 
-    let host_res = request.headers.get("host");
-    let path = &request.request_line.request_target;
-    let response = host_res.map_or_else(
-        || {
-            html_response(
-                StatusCode::BadRequest,
-                "<html><body><h1>Bad Request</h1></body></html>",
-            )
-        },
-        |host| {
-            let mut headers = Headers::new();
-            headers.insert("Location", format!("https://{host}{path}"));
-            Response {
-                status: StatusCode::MovedPermanently,
-                headers,
-                body: b"".to_vec(),
-            }
-        },
-    );
+    let redirect = RedirectToHttps::new(settings.https_redirect_port);
+    let response = redirect.response(&request).unwrap_or_else(|| {
+        logged_error_response(
+            shared,
+            StatusCode::BadRequest,
+            "<html><body><h1>Bad Request</h1></body></html>",
+            "cannot redirect a request without a Host header",
+        )
+    });
 
     write_response(&mut stream, response).await?;
     Ok(())
@@ -407,6 +858,11 @@ async fn handle_redirect<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
 /// Handles a singular request from the associated Tcp Stream.
 ///
+/// Bytes received after the request are kept in `pending` for the next call. With `FlushStrategy::Coalesce` the response is only flushed once no such bytes remain.
+///
+/// Returns whether the connection stays open, is closed or turns into a `CONNECT` tunnel. It is closed if the request or the response contain `Connection: close`,
+/// if `remaining_requests` shows this to be the last request allowed on it or if the server is shutting down, in which case the response says so.
+///
 /// # Errors
 ///
 /// Throws an `HttpError` if parsing fails or if a timeout occurs.
@@ -414,76 +870,359 @@ async fn process_request<S: AsyncRead + AsyncWrite + Unpin + Send>(
     mut stream: &mut S,
     router: &Router,
     settings: &Settings,
-) -> Result<bool, HttpError> {
+    shared: &SharedState,
+    context: &RequestContext,
+    remaining_requests: usize,
+    pending: &mut Vec<u8>,
+) -> Result<ConnectionState, HttpError> {
     let keep_alive_timeout_value = settings.keep_alive_timeout;
     let keep_alive_timeout = Duration::from_secs(keep_alive_timeout_value);
-    let request_future = request_from_reader(&mut stream, settings);
+    let body_limit = |request_line: &RequestLine| router.body_limit(request_line);
+    let mut parsed_line = None;
+    let request_future = request_from_connection(
+        &mut stream,
+        settings,
+        &shared.memory,
+        pending,
+        &body_limit,
+        &mut parsed_line,
+    );
     let request_res = timeout(keep_alive_timeout, request_future).await;
-    let request = match request_res {
+    let mut request = match request_res {
         Ok(Ok(req)) => req,
         Ok(Err(HttpError::UnexpectedEOF)) => {
-            return Ok(true);
-        }
-        Ok(Err(HttpError::Timeout)) => {
-            let html = "<html><body><h1>Request timed out</h1></body></html>";
-            let response = html_response(StatusCode::RequestTimeout, html);
-
-            write_response(stream, response).await?;
-            return Ok(false);
-        }
-        Ok(Err(_e)) => {
-            let html = "<html><body><h1>Bad Request</h1></body></html>";
-            let response = html_response(StatusCode::BadRequest, html);
-
-            write_response(stream, response).await?;
-            return Ok(false);
+            return Ok(ConnectionState::KeepOpen);
         }
+        Ok(Err(e)) => return reject_request(stream, router, shared, parsed_line, &e).await,
         Err(_) => {
-            let html = "<html><body><h1>Bad Request</h1></body></html>";
-            let response = html_response(StatusCode::BadRequest, html);
-            write_response(stream, response).await?;
-            return Ok(false);
+            let error = HttpError::Timeout;
+            return reject_request(stream, router, shared, parsed_line, &error).await;
         }
     };
 
-    // FIXME We should probably have a dedicated place to manage headers
-    let keep_alive = Headers::get(&request.headers, "connection") != Some("close");
-
-    let response = router.call(request).await?;
-    write_status_line(&mut stream, response.status).await?;
-    let mut headers = response.headers;
-    write_headers(&mut stream, &mut headers).await?;
-    stream.write_all(&response.body).await?;
-
-    let connection_value = headers.get("connection");
-    if connection_value == Some("close") {
+    let started_at = Instant::now();
+    request.context = RequestContext {
+        started_at: Some(started_at),
+        trust_forwarded_proto: settings.trust_forwarded_proto,
+        ..context.clone()
+    };
+    for header in &settings.strip_request_headers {
+        request.headers.remove(&header.to_lowercase());
+    }
+    if request.request_line.method == Method::Connect
+        && router.allows_tunnel(&request.request_line.request_target)
+    {
+        let connect_timeout = Duration::from_secs(settings.connection_timeout);
+        return open_tunnel(
+            stream,
+            shared,
+            &request.request_line.request_target,
+            connect_timeout,
+        )
+        .await;
+    }
+    if router.upgrades(request.request_line.path()) {
+        return accept_websocket(stream, router, shared, request, started_at).await;
+    }
+    // Handlers consume the request, the body stays accounted for until the response is written.
+    let _reservation = request.take_reservation();
+
+    let client_close = requests_close(&request.headers);
+    let is_head = request.request_line.method == Method::Head;
+    let accept_encoding = request.headers.get("accept-encoding").map(str::to_string);
+    let observed = (router.observes_requests() || shared.access_log.is_some())
+        .then(|| request.request_line.clone());
+
+    let mut response = if let Some(response) = rejected_upgrade(settings, &request) {
+        response
+    } else if let Some(_permit) = shared.inflight.acquire().await {
+        call_handler(router, request, shared).await
+    } else {
+        Response::service_unavailable(None)
+    };
+    response = encode_response(settings, response, accept_encoding.as_deref(), started_at);
+    // A shutdown started while the handler ran still ends the connection after this response.
+    let close_requested = client_close || shared.shutting_down.load(Ordering::SeqCst);
+    let keep_open = decide_keep_alive(close_requested, &mut response, remaining_requests);
+    if keep_open && settings.keep_alive_header {
+        response.headers.insert(
+            "keep-alive",
+            format!(
+                "timeout={}, max={}",
+                settings.keep_alive_timeout,
+                remaining_requests - 1
+            ),
+        );
+    }
+    if let Some(request_line) = &observed {
+        record_request(
+            router,
+            shared,
+            request_line,
+            response.status,
+            started_at.elapsed(),
+        );
+    }
+    if is_head {
+        write_head_response_without_flush(&mut stream, response).await?;
+    } else {
+        write_response_without_flush(&mut stream, response).await?;
+    }
+    if !keep_open || pending.is_empty() || settings.flush_strategy == FlushStrategy::Immediate {
         stream.flush().await?;
-        Ok(false)
+    }
+
+    Ok(if keep_open {
+        ConnectionState::KeepOpen
     } else {
-        if !keep_alive {
-            stream.flush().await?;
-            return Ok(false);
+        ConnectionState::Close
+    })
+}
+
+/// Answers a request that could not be parsed, recording it if its request line was parsed before it was rejected.
+///
+/// # Errors
+///
+/// Throws an `HttpError` if writing the response fails.
+async fn reject_request<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    router: &Router,
+    shared: &SharedState,
+    parsed_line: Option<(RequestLine, Instant)>,
+    error: &HttpError,
+) -> Result<ConnectionState, HttpError> {
+    let response = rejected_request_response(error, shared);
+    if let Some((request_line, parsed_at)) = parsed_line {
+        record_request(
+            router,
+            shared,
+            &request_line,
+            response.status,
+            parsed_at.elapsed(),
+        );
+    }
+    write_response(stream, response).await?;
+    Ok(ConnectionState::Close)
+}
+
+/// Helper function calling the router, answering handler errors and panics with `500 Internal Server Error`.
+///
+/// A panicking handler only fails its own request, the panic is logged and the connection closed after the response.
+async fn call_handler(router: &Router, request: Request, shared: &SharedState) -> Response {
+    let html = "<html><body><h1>Internal Server Error</h1></body></html>";
+    match AssertUnwindSafe(router.call(request)).catch_unwind().await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => logged_error_response(shared, StatusCode::InternalServerError, html, e),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            let error = format!("handler panicked: {message}");
+            logged_error_response(shared, StatusCode::InternalServerError, html, error)
         }
-        stream.flush().await?;
-        Ok(true)
     }
 }
 
-/// Helper function to group together the write operations given a TCP Stream and a response object.
+/// Helper function passing a completed request to the access log and the callback registered on the router.
+fn record_request(
+    router: &Router,
+    shared: &SharedState,
+    request_line: &RequestLine,
+    status: StatusCode,
+    duration: Duration,
+) {
+    if let Some(log) = &shared.access_log {
+        log.record(request_line, status, duration);
+    }
+    router.request_completed(request_line, status, duration);
+}
+
+/// Helper function compressing the response and reporting the handler duration, as far as enabled in the settings.
+fn encode_response(
+    settings: &Settings,
+    mut response: Response,
+    accept_encoding: Option<&str>,
+    started_at: Instant,
+) -> Response {
+    if settings.compression.enabled {
+        let accept_encoding = accept_encoding.unwrap_or_default();
+        response = compress_response(response, accept_encoding, settings.compression.min_size);
+    }
+    if settings.server_timing_header {
+        let duration = started_at.elapsed().as_secs_f64() * 1000.0;
+        response
+            .headers
+            .append("server-timing", format!("app;dur={duration:.1}"));
+    }
+    response
+}
+
+/// Helper function creating the closing response for a request that could not be parsed.
+fn rejected_request_response(error: &HttpError, shared: &SharedState) -> Response {
+    let (status, html) = rejection_status(error);
+    logged_error_response(shared, status, html, error)
+}
+
+/// Helper function choosing the status and built-in page answering a request that could not be parsed.
+const fn rejection_status(error: &HttpError) -> (StatusCode, &'static str) {
+    match error {
+        HttpError::Timeout => (
+            StatusCode::RequestTimeout,
+            "<html><body><h1>Request timed out</h1></body></html>",
+        ),
+        HttpError::ContentTooLarge => (
+            StatusCode::ContentTooLarge,
+            "<html><body><h1>Content Too Large</h1></body></html>",
+        ),
+        HttpError::MemoryLimitExceeded => (
+            StatusCode::ServiceUnavailable,
+            "<html><body><h1>Service Unavailable</h1></body></html>",
+        ),
+        HttpError::ExpectationFailed => (
+            StatusCode::ExpectationFailed,
+            "<html><body><h1>Expectation Failed</h1></body></html>",
+        ),
+        _ => (
+            StatusCode::BadRequest,
+            "<html><body><h1>Bad Request</h1></body></html>",
+        ),
+    }
+}
+
+/// Helper function creating a closing error response tagged with a new request id in its `X-Request-Id` header.
+///
+/// The error is logged with the same id, so the line belonging to a response a client reports can be found.
+fn logged_error_response(
+    shared: &SharedState,
+    status: StatusCode,
+    builtin: &str,
+    error: impl Display,
+) -> Response {
+    let request_id = next_request_id();
+    shared.error_log.record(&request_id, error);
+    let mut response = error_response(&shared.error_pages, status, builtin);
+    response.headers.insert("x-request-id", request_id);
+    response
+}
+
+/// Helper function creating a closing error response, using the custom page for the status if one is configured.
+fn error_response(error_pages: &ErrorPages, status: StatusCode, builtin: &str) -> Response {
+    let mut response = error_pages.response(status, builtin);
+    response.headers.insert("connection", "close");
+    response
+}
+
+/// Connects to the authority of a `CONNECT` request and confirms the tunnel with `200 Connection Established`.
+///
+/// Answers with `502 Bad Gateway` and closes the connection if the authority cannot be reached, or with
+/// `504 Gateway Timeout` if connecting takes longer than `connect_timeout`. Both are tagged with a request id.
 ///
 /// # Errors
 ///
-/// Throws an `HttpError` if the write process fails.
-async fn write_response<S: AsyncRead + AsyncWrite + Unpin>(
-    mut stream: &mut S,
-    response: Response,
-) -> Result<(), HttpError> {
-    write_status_line(&mut stream, response.status).await?;
-    let mut headers = response.headers;
-    write_headers(&mut stream, &mut headers).await?;
-    stream.write_all(&response.body).await?;
+/// Throws an `HttpError` if writing the response fails.
+async fn open_tunnel<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    shared: &SharedState,
+    authority: &str,
+    connect_timeout: Duration,
+) -> Result<ConnectionState, HttpError> {
+    let upstream = match timeout(connect_timeout, TcpStream::connect(authority)).await {
+        Ok(Ok(upstream)) => upstream,
+        Ok(Err(e)) => {
+            let html = "<html><body><h1>Bad Gateway</h1></body></html>";
+            let error = format!("connecting the tunnel to {authority} failed: {e}");
+            let response = logged_error_response(shared, StatusCode::BadGateway, html, error);
+            write_response(stream, response).await?;
+            return Ok(ConnectionState::Close);
+        }
+        Err(_elapsed) => {
+            let html = "<html><body><h1>Gateway Timed out</h1></body></html>";
+            let error = format!("connecting the tunnel to {authority} timed out");
+            let response = logged_error_response(shared, StatusCode::GatewayTimeout, html, error);
+            write_response(stream, response).await?;
+            return Ok(ConnectionState::Close);
+        }
+    };
+    stream
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
     stream.flush().await?;
-    Ok(())
+    Ok(ConnectionState::Tunnel(upstream))
+}
+
+/// Answers the WebSocket opening handshake of a request for a WebSocket route with `101 Switching Protocols`.
+///
+/// An invalid handshake is answered with `400 Bad Request` and the connection closed.
+///
+/// # Errors
+///
+/// Throws an `HttpError` if writing the response fails.
+async fn accept_websocket<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    router: &Router,
+    shared: &SharedState,
+    request: Request,
+    started_at: Instant,
+) -> Result<ConnectionState, HttpError> {
+    let request_line = request.request_line.clone();
+    let (status, state) = match websocket::accept(&request, &mut *stream).await {
+        Ok(_) => (
+            StatusCode::SwitchingProtocols,
+            ConnectionState::WebSocket(Box::new(request)),
+        ),
+        Err(e @ HttpError::InvalidWebSocketHandshake(_)) => {
+            let response = rejected_request_response(&e, shared);
+            write_response(&mut *stream, response).await?;
+            (StatusCode::BadRequest, ConnectionState::Close)
+        }
+        Err(e) => return Err(e),
+    };
+    stream.flush().await?;
+    record_request(router, shared, &request_line, status, started_at.elapsed());
+    Ok(state)
+}
+
+/// Returns a `501 Not Implemented` response if the request asks for a protocol upgrade and the policy rejects them.
+fn rejected_upgrade(settings: &Settings, request: &Request) -> Option<Response> {
+    if settings.upgrade_policy != UpgradePolicy::Reject || request.headers.get("upgrade").is_none()
+    {
+        return None;
+    }
+    let html = "<html><body><h1>Not Implemented</h1></body></html>";
+    Some(html_response(StatusCode::NotImplemented, html))
+}
+
+/// Decides whether the connection stays open after the response and announces the decision in its `Connection` header.
+///
+/// HTTP/1.1 connections persist by default. The connection is closed if the client or the handler asked for it with
+/// `Connection: close`, or if the request is the last one allowed on the connection.
+fn decide_keep_alive(
+    client_close: bool,
+    response: &mut Response,
+    remaining_requests: usize,
+) -> bool {
+    let handler_close = requests_close(&response.headers);
+    let keep_open = !client_close && !handler_close && remaining_requests > 1;
+    // A `close` set by the handler already announces the decision.
+    if !handler_close {
+        let connection = if keep_open { "keep-alive" } else { "close" };
+        response.headers.insert("connection", connection);
+    }
+    keep_open
+}
+
+/// Whether the passed headers contain a `close` connection option, matched case-insensitively.
+fn requests_close(headers: &Headers) -> bool {
+    headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("connection"))
+        .any(|(_, value)| {
+            value
+                .split(',')
+                .any(|option| option.trim().eq_ignore_ascii_case("close"))
+        })
 }
 
 /// Helper function to import the config and set defaults.
@@ -497,6 +1236,7 @@ pub fn build_config() -> Result<Config, ConfigError> {
         .add_source(config_source)
         .set_default("port", 443)?
         .set_default("http_port", 80)?
+        .set_default("https_redirect_port", 443)?
         .set_default("max_clients", 5000)?
         .set_default("cert_key_dir", "certs/cert.pem")?
         .set_default("tls_key_dir", "certs/cert.key.pem")?
@@ -504,32 +1244,88 @@ pub fn build_config() -> Result<Config, ConfigError> {
         .set_default("ip_connection_limit", 20)?
         .set_default("keep_alive_timeout", 15)?
         .set_default("parsing_timeout", 30)?
+        .set_default("header_timeout", 10)?
         .set_default("request_size_limit_in_mib", 16)?
+        .set_default("global_body_limit_in_mib", 256)?
+        .set_default("body_spill_threshold_in_mib", 0)?
+        .set_default("read_buffer_size_in_kib", 8)?
+        .set_default("max_inflight_requests", 5000)?
+        .set_default("inflight_queue_timeout", 10)?
         .set_default("header_size_limit_in_kib", 32)?
         .set_default("max_header_size", 72)?
+        .set_default("max_chunk_size_in_kib", 1024)?
+        .set_default("max_keepalive_requests", 1000)?
+        .set_default("keep_alive_header", false)?
+        .set_default("server_timing_header", false)?
+        .set_default("strip_request_headers", Vec::<String>::new())?
+        .set_default("alpn_protocols", vec!["http/1.1"])?
+        .set_default("trust_forwarded_proto", false)?
+        .set_default("compression.enabled", false)?
+        .set_default("compression.min_size", 1024)?
+        .set_default("error_pages", HashMap::<String, String>::new())?
+        .set_default("flush_strategy", "immediate")?
+        .set_default("upgrade_policy", "ignore")?
+        .set_default("access_log", "off")?
         .set_default("connection_timeout", 120)?
+        .set_default("shutdown_drain_timeout", 30)?
+        .set_default("tunnel_timeout", 3600)?
         .build()?;
     Ok(config)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{sync::Arc, time::Duration};
+    use std::{
+        collections::HashMap,
+        io,
+        pin::Pin,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
 
-    use config::{Config, File};
+    use bytes::Bytes;
+    use config::{Config, ConfigError, File};
+    use flate2::read::GzDecoder;
     use reqwest::Client;
     use rustls::{
         ClientConfig, ProtocolVersion, RootCertStore, ServerConfig,
         pki_types::{PrivatePkcs8KeyDer, ServerName},
     };
-    use tokio::time::{sleep, timeout};
+    use tokio::{
+        io::{
+            AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+            ReadBuf, duplex,
+        },
+        net::{TcpListener, TcpStream},
+        sync::{Notify, mpsc},
+        time::{Instant, sleep, timeout},
+    };
     use tokio_rustls::{TlsAcceptor, TlsConnector};
 
     use crate::{
-        http::response::{StatusCode, html_response},
+        http::{
+            headers::Headers,
+            limits::ParseLimits,
+            request::HttpError,
+            request_line::Method,
+            response::{Response, StatusCode, html_response, response_from_reader},
+        },
         runtime::{
+            context::RequestContext,
+            inflight::InflightLimiter,
+            log::{AccessLog, ErrorLog},
+            memory::MemoryBudget,
             router::Router,
-            server::{ConnectionLimiter, serve},
+            server::{
+                ConnectionLimiter, ConnectionState, FlushStrategy, Settings, SharedState,
+                UpgradePolicy, accept_tls, build_tls_config, connection_context, handle,
+                handle_redirect, process_request, serve,
+            },
+            test_client,
         },
     };
 
@@ -540,6 +1336,34 @@ mod tests {
         Router::new()
     }
 
+    /// Helper function reading a single response with a `content-length` from the stream.
+    async fn read_response<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> String {
+        let mut response = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            if let Some(value) = line.strip_prefix("content-length: ") {
+                content_length = value.trim().parse().unwrap();
+            }
+            response.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).await.unwrap();
+        response.push_str(&String::from_utf8(body).unwrap());
+        response
+    }
+
+    /// Helper function that reads the settings from the config file.
+    fn test_settings() -> Settings {
+        let config_source = File::with_name("config");
+        let config = Config::builder().add_source(config_source).build().unwrap();
+        config.try_deserialize().unwrap()
+    }
+
     #[tokio::test]
     async fn server_can_establish_connection() {
         let mut router = serve_router();
@@ -588,32 +1412,413 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn endpoints_write_correct_response() {
-        let router = serve_router();
+    async fn test_client_receives_routed_response() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
 
         let config_source = File::with_name("config");
         let config = Config::builder()
             .add_source(config_source)
-            .set_override("port", 1028)
+            .set_override("port", 0)
             .unwrap()
-            .set_override("http_port", 1029)
+            .set_override("http_port", 0)
             .unwrap()
             .build()
             .unwrap();
         let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        assert_ne!(port, 0);
+        assert_ne!(port, server.http_local_addr().unwrap().port());
+        let response = timeout(
+            Duration::from_secs(5),
+            test_client::send(
+                port,
+                "GET /test HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            ),
+        )
+        .await
+        .expect("Test timed out");
 
-        let base_url = format!("https://127.0.0.1:{}", 1028);
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.headers.get("content-type"), Some("text/html"));
+        assert_eq!(response.body, b"<h1>All good!</h1>");
+        server.close();
+    }
 
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .timeout(Duration::from_secs(5))
+    #[tokio::test]
+    async fn head_request_receives_headers_without_body() {
+        let mut router = serve_router();
+        router.route("/mp4", |_req| async {
+            Response::builder()
+                .header("content-type", "video/mp4")
+                .body(vec![0u8; 4096])
+                .build()
+        });
+        let config = Config::builder()
+            .add_source(File::with_name("config"))
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
             .build()
             .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
 
-        let client = client.clone();
-        let url = format!("{base_url}/yourproblem");
+        let mut client = test_client::connect(port).await;
+        client
+            .write_all(b"HEAD /mp4 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = Vec::new();
+        // The server closes the connection without a TLS close_notify, so the read ends in an error after the response.
+        let _ = timeout(Duration::from_secs(5), client.read_to_end(&mut raw))
+            .await
+            .expect("Test timed out");
+        let raw = String::from_utf8(raw).unwrap();
 
-        let task = tokio::spawn(async move {
+        assert!(raw.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(raw.contains("content-length: 4096\r\n"));
+        assert!(raw.ends_with("\r\n\r\n"));
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn active_connections_follow_open_connections() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let config = Config::builder()
+            .add_source(File::with_name("config"))
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            let mut client = test_client::connect(port).await;
+            client
+                .write_all(b"GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let response = response_from_reader(&mut client).await.unwrap();
+            assert_eq!(response.status, StatusCode::Ok);
+            clients.push(client);
+        }
+        assert_eq!(server.active_connections(), 2);
+
+        drop(clients.pop());
+        timeout(Duration::from_secs(5), async {
+            while server.active_connections() != 1 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Connection was not closed");
+        drop(clients);
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn connections_beyond_max_clients_wait_to_be_accepted() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let config = Config::builder()
+            .add_source(File::with_name("config"))
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .set_override("max_clients", 2)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            clients.push(test_client::connect(port).await);
+        }
+        let queued = tokio::spawn(test_client::send(port, request));
+        sleep(Duration::from_millis(200)).await;
+
+        assert!(!queued.is_finished());
+        assert_eq!(server.active_connections(), 2);
+        drop(clients.pop());
+        let response = timeout(Duration::from_secs(5), queued)
+            .await
+            .expect("Queued connection was not accepted")
+            .unwrap();
+        assert_eq!(response.status, StatusCode::Ok);
+        drop(clients);
+        server.close();
+    }
+
+    #[tokio::test]
+    #[allow(clippy::panic)]
+    async fn panicking_handler_is_answered_with_500() {
+        let mut router = serve_router();
+        router.route("/boom", |_req| async { panic!("boom") });
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let config = Config::builder()
+            .add_source(File::with_name("config"))
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        let boom = timeout(
+            Duration::from_secs(5),
+            test_client::send(port, "GET /boom HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+        )
+        .await
+        .expect("Test timed out");
+        let test = timeout(
+            Duration::from_secs(5),
+            test_client::send(port, "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+        )
+        .await
+        .expect("Test timed out");
+
+        assert_eq!(boom.status, StatusCode::InternalServerError);
+        assert!(boom.headers.get("x-request-id").is_some());
+        assert_eq!(boom.headers.get("connection"), Some("close"));
+        assert_eq!(test.status, StatusCode::Ok);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_in_flight_request() {
+        let started = Arc::new(Notify::new());
+        let mut router = serve_router();
+        let handler_started = Arc::clone(&started);
+        router.route("/slow", move |_req| {
+            let handler_started = Arc::clone(&handler_started);
+            async move {
+                handler_started.notify_one();
+                sleep(Duration::from_millis(500)).await;
+                html_response(StatusCode::Ok, "<h1>Finished</h1>")
+            }
+        });
+
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        let request = tokio::spawn(test_client::send(
+            port,
+            "GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        ));
+        timeout(Duration::from_secs(5), started.notified())
+            .await
+            .expect("Handler was not called");
+        timeout(Duration::from_secs(5), server.shutdown())
+            .await
+            .expect("Shutdown timed out");
+
+        let response = request.await.unwrap();
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.body, b"<h1>Finished</h1>");
+        assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_keep_alive_connection_after_in_flight_response() {
+        let started = Arc::new(Notify::new());
+        let mut router = serve_router();
+        let handler_started = Arc::clone(&started);
+        router.route("/slow", move |_req| {
+            let handler_started = Arc::clone(&handler_started);
+            async move {
+                handler_started.notify_one();
+                sleep(Duration::from_millis(500)).await;
+                html_response(StatusCode::Ok, "<h1>Finished</h1>")
+            }
+        });
+
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        let mut client = test_client::connect(port).await;
+        client
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(5), started.notified())
+            .await
+            .expect("Handler was not called");
+        let shutdown = tokio::spawn(server.shutdown());
+
+        let response = response_from_reader(&mut client).await.unwrap();
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.headers.get("connection"), Some("close"));
+
+        let mut rest = Vec::new();
+        // The server closes without a close_notify, so only the missing data matters.
+        let _ = timeout(Duration::from_secs(5), client.read_to_end(&mut rest))
+            .await
+            .expect("Connection stayed open after the response");
+        assert!(rest.is_empty());
+        timeout(Duration::from_secs(5), shutdown)
+            .await
+            .expect("Shutdown timed out")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn websocket_route_takes_over_connection_after_handshake() {
+        let mut router = serve_router();
+        router.websocket("/chat/:room", |req, mut connection| async move {
+            let mut received = [0u8; 4];
+            connection.read_exact(&mut received).await.unwrap();
+            let room = req.param("room").unwrap_or_default().to_string();
+            connection.write_all(room.as_bytes()).await.unwrap();
+            connection.write_all(&received).await.unwrap();
+            connection.flush().await.unwrap();
+        });
+
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+
+        let mut client = BufReader::new(test_client::connect(port).await);
+        // The first frame is sent along with the handshake, before the server answered it.
+        client
+            .write_all(
+                b"GET /chat/lobby HTTP/1.1\r\n\
+                Host: localhost\r\n\
+                Upgrade: websocket\r\n\
+                Connection: Upgrade\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                Sec-WebSocket-Version: 13\r\n\
+                \r\n\
+                ping",
+            )
+            .await
+            .unwrap();
+        client.flush().await.unwrap();
+
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            client.read_line(&mut line).await.unwrap();
+            head.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(head.contains("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut rest = Vec::new();
+        // The server closes without a close_notify once the handler returns, so only the received data matters.
+        let _ = timeout(Duration::from_secs(5), client.read_to_end(&mut rest))
+            .await
+            .expect("Connection stayed open after the handler returned");
+        assert_eq!(rest, b"lobbyping");
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn shutdown_runs_hooks_in_registration_order() {
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, serve_router())
+            .await
+            .expect("Failed to start server");
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for hook in ["metrics", "database"] {
+            let order = Arc::clone(&order);
+            server.on_shutdown(move || order.lock().unwrap().push(hook));
+        }
+        assert!(order.lock().unwrap().is_empty());
+
+        timeout(Duration::from_secs(5), server.shutdown())
+            .await
+            .expect("Shutdown timed out");
+
+        assert_eq!(*order.lock().unwrap(), ["metrics", "database"]);
+    }
+
+    #[tokio::test]
+    async fn endpoints_write_correct_response() {
+        let router = serve_router();
+
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 1028)
+            .unwrap()
+            .set_override("http_port", 1029)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+
+        let base_url = format!("https://127.0.0.1:{}", 1028);
+
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        let client = client.clone();
+        let url = format!("{base_url}/yourproblem");
+
+        let task = tokio::spawn(async move {
             let resp = client.get(&url).send().await.expect("Request failed");
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
@@ -697,7 +1902,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let start = std::time::Instant::now();
+        let start = Instant::now();
 
         let tasks: Vec<_> = (0..CONCURRENT_REQUESTS)
             .map(|_| {
@@ -783,7 +1988,7 @@ mod tests {
             .with_single_cert(vec![cert.der().clone()], private_key_der.into())
             .unwrap();
 
-        let (client, server) = tokio::io::duplex(65536);
+        let (client, server) = duplex(65536);
         let acceptor = TlsAcceptor::from(Arc::new(server_config));
 
         let connector = TlsConnector::from(Arc::new(client_config));
@@ -800,4 +2005,1508 @@ mod tests {
         let result = client_stream.get_ref().1.protocol_version().unwrap();
         assert_eq!(result, ProtocolVersion::TLSv1_3);
     }
+
+    #[tokio::test]
+    async fn expect_continue_with_oversized_body_responds_413_without_100() {
+        let router = serve_router();
+        let settings = test_settings();
+        let (mut client, mut server_stream) = duplex(4096);
+
+        let request = "POST /upload HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Content-Length: 999999999\r\n\
+            Expect: 100-continue\r\n\
+            \r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let keep_alive = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            1,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        drop(server_stream);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+
+        assert!(matches!(keep_alive, ConnectionState::Close));
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+        assert!(!response.contains("100 Continue"));
+    }
+
+    #[tokio::test]
+    async fn route_body_limit_applies_before_body_is_read() {
+        let requests = [
+            "POST /json HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Content-Length: 32\r\n\
+            Expect: 100-continue\r\n\
+            \r\n",
+            "POST /json HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            20\r\n\
+            xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx\r\n\
+            0\r\n\
+            \r\n",
+        ];
+        for request in requests {
+            let mut router = serve_router();
+            router.route_with_limit("/json", 8, |_req| async {
+                html_response(StatusCode::Ok, "json")
+            });
+            let settings = test_settings();
+            let (mut client, mut server_stream) = duplex(4096);
+            client.write_all(request.as_bytes()).await.unwrap();
+
+            let keep_alive = process_request(
+                &mut server_stream,
+                &router,
+                &settings,
+                &SharedState::new(&settings),
+                &RequestContext::default(),
+                5,
+                &mut Vec::new(),
+            )
+            .await
+            .unwrap();
+            drop(server_stream);
+
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+
+            assert!(matches!(keep_alive, ConnectionState::Close), "{request}");
+            assert!(
+                response.starts_with("HTTP/1.1 413 Content Too Large\r\n"),
+                "{response}"
+            );
+            assert!(!response.contains("100 Continue"));
+        }
+    }
+
+    #[tokio::test]
+    async fn channel_response_is_written_as_chunks_in_order() {
+        let mut router = serve_router();
+        router.route("/stream", |_req| async {
+            let (sender, receiver) = mpsc::channel(4);
+            tokio::spawn(async move {
+                for data in ["first", "second", "third"] {
+                    sender.send(Bytes::from(data)).await.unwrap();
+                }
+            });
+            Response::from_channel(StatusCode::Ok, Headers::new(), receiver)
+        });
+        let settings = test_settings();
+        let (mut client, mut server_stream) = duplex(4096);
+
+        let request = "GET /stream HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            1,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        drop(server_stream);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("transfer-encoding: chunked\r\n"));
+        assert!(response.ends_with("\r\n\r\n5\r\nfirst\r\n6\r\nsecond\r\n5\r\nthird\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn chunked_writer_streams_output_of_unknown_length() {
+        let mut router = serve_router();
+        router.route("/count", |req| async move {
+            let up_to: usize = req
+                .request_line
+                .query
+                .get("to")
+                .and_then(|to| to.parse().ok())
+                .unwrap_or_default();
+            let (response, writer) = Response::chunked(StatusCode::Ok, Headers::new());
+            tokio::spawn(async move {
+                for i in 1..=up_to {
+                    writer.write("*".repeat(i)).await.unwrap();
+                }
+            });
+            response
+        });
+        let settings = test_settings();
+        let (mut client, mut server_stream) = duplex(4096);
+
+        let request = "GET /count?to=4 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            1,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        drop(server_stream);
+
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::Ok);
+        assert_eq!(response.headers.get("transfer-encoding"), Some("chunked"));
+        assert_eq!(response.headers.get("content-length"), None);
+        assert_eq!(response.body, b"**********");
+    }
+
+    /// Helper function performing a TLS handshake over an in-memory stream with the passed ALPN protocols on both sides.
+    async fn tls_handshake(
+        alpn_protocols: &[&[u8]],
+    ) -> tokio_rustls::server::TlsStream<tokio::io::DuplexStream> {
+        let CertifiedKey { cert, signing_key } =
+            generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+        let mut root_store = RootCertStore::empty();
+        root_store.add(cert.der().clone()).unwrap();
+        let mut client_config = ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        client_config.alpn_protocols = alpn_protocols.iter().map(|p| p.to_vec()).collect();
+
+        let private_key_der = PrivatePkcs8KeyDer::from(signing_key.serialize_der());
+        let mut server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.der().clone()], private_key_der.into())
+            .unwrap();
+        server_config.alpn_protocols = alpn_protocols.iter().map(|p| p.to_vec()).collect();
+
+        let (client, server) = duplex(65536);
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let server_name = ServerName::try_from("localhost").unwrap();
+
+        let (server_result, client_result) = tokio::join!(
+            TlsAcceptor::accept(&acceptor, server),
+            TlsConnector::connect(&connector, server_name, client),
+        );
+        client_result.unwrap();
+        server_result.unwrap()
+    }
+
+    #[tokio::test]
+    async fn context_reports_negotiated_alpn_protocol() {
+        let server_stream = tls_handshake(&[b"http/1.1"]).await;
+
+        let context = connection_context(&server_stream).unwrap();
+
+        assert_eq!(context.alpn_protocol.as_deref(), Some("http/1.1"));
+        assert!(context.is_secure);
+    }
+
+    #[test]
+    fn tls_config_offers_configured_alpn_protocols() {
+        let mut settings = test_settings();
+        assert_eq!(
+            build_tls_config(&settings).unwrap().alpn_protocols,
+            vec![b"http/1.1".to_vec()]
+        );
+
+        settings.alpn_protocols = vec!["h2".to_string(), "http/1.1".to_string()];
+        assert_eq!(
+            build_tls_config(&settings).unwrap().alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn unsupported_alpn_protocol_is_rejected() {
+        let server_stream = tls_handshake(&[b"h2"]).await;
+
+        let result = connection_context(&server_stream);
+
+        assert!(matches!(result, Err(HttpError::UnsupportedProtocol(_))));
+    }
+
+    #[tokio::test]
+    async fn aborted_tls_handshake_is_reported_as_http_error() {
+        let CertifiedKey { cert, signing_key } =
+            generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let private_key_der = PrivatePkcs8KeyDer::from(signing_key.serialize_der());
+        let server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.der().clone()], private_key_der.into())
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+
+        let (mut client, server) = duplex(1024);
+        client
+            .write_all(b"\x16\x03\x01\x00\x05hello")
+            .await
+            .unwrap();
+        drop(client);
+
+        let result = accept_tls(&acceptor, server).await;
+
+        assert!(matches!(result, Err(HttpError::TlsHandshake(_))));
+    }
+
+    #[tokio::test]
+    async fn plaintext_request_on_tls_port_gets_helpful_response() {
+        let router = serve_router();
+
+        let config_source = File::with_name("config");
+        let config = Config::builder()
+            .add_source(config_source)
+            .set_override("port", 1034)
+            .unwrap()
+            .set_override("http_port", 1035)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = serve(config, router).await.expect("Failed to start server");
+
+        let mut client = TcpStream::connect("127.0.0.1:1034").await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        timeout(Duration::from_secs(5), client.read_to_string(&mut response))
+            .await
+            .expect("Test timed out")
+            .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(response.ends_with("Please connect using https:// instead."));
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn connection_closes_after_max_keepalive_requests() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<html><body>ok</body></html>")
+        });
+        let config_source = File::with_name("config");
+        let settings: Settings = Config::builder()
+            .add_source(config_source)
+            .set_override("max_keepalive_requests", 2)
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let (client, server_stream) = duplex(4096);
+        let handle_task = tokio::spawn(async move {
+            handle(
+                server_stream,
+                &router,
+                &settings,
+                &SharedState::new(&settings),
+                &RequestContext::default(),
+            )
+            .await
+        });
+        let mut client = BufReader::new(client);
+        let request = b"GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        client.write_all(request).await.unwrap();
+        let first = read_response(&mut client).await;
+        client.write_all(request).await.unwrap();
+        let second = read_response(&mut client).await;
+
+        assert!(!first.contains("connection: close"));
+        assert!(second.contains("connection: close"));
+        timeout(Duration::from_secs(5), handle_task)
+            .await
+            .expect("Connection was not closed")
+            .unwrap()
+            .unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert!(test_settings().validate().is_ok());
+    }
+
+    #[test]
+    fn contradicting_settings_are_rejected() {
+        let mut same_ports = test_settings();
+        same_ports.http_port = same_ports.port;
+        let mut chunk_exceeds_request = test_settings();
+        chunk_exceeds_request.request_size_limit_in_mib = 1;
+        chunk_exceeds_request.max_chunk_size_in_kib = 2048;
+        let mut request_exceeds_budget = test_settings();
+        request_exceeds_budget.global_body_limit_in_mib =
+            request_exceeds_budget.request_size_limit_in_mib - 1;
+
+        for settings in [same_ports, chunk_exceeds_request, request_exceeds_budget] {
+            assert!(matches!(settings.validate(), Err(ConfigError::Message(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_rejects_contradicting_settings() {
+        let config = Config::builder()
+            .add_source(File::with_name("config"))
+            .set_override("port", 0)
+            .unwrap()
+            .set_override("http_port", 0)
+            .unwrap()
+            .set_override("max_keepalive_requests", 0)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = serve(config, serve_router()).await.unwrap_err();
+
+        assert!(error.to_string().contains("max_keepalive_requests"));
+    }
+
+    #[tokio::test]
+    async fn pipelined_request_beyond_max_keepalive_requests_is_not_answered() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<html><body>ok</body></html>")
+        });
+        let mut settings = test_settings();
+        settings.max_keepalive_requests = 2;
+        let (client, server_stream) = duplex(4096);
+        let handle_task = tokio::spawn(async move {
+            handle(
+                server_stream,
+                &router,
+                &settings,
+                &SharedState::new(&settings),
+                &RequestContext::default(),
+            )
+            .await
+        });
+        let mut client = BufReader::new(client);
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        client
+            .write_all(request.repeat(3).as_bytes())
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(5), handle_task)
+            .await
+            .expect("Connection was not closed")
+            .unwrap()
+            .unwrap();
+        let mut responses = String::new();
+        client.read_to_string(&mut responses).await.unwrap();
+
+        assert_eq!(responses.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+        assert_eq!(responses.matches("connection: keep-alive\r\n").count(), 1);
+        assert!(responses.ends_with("connection: close\r\n\r\n<html><body>ok</body></html>"));
+    }
+
+    #[tokio::test]
+    async fn timed_out_request_response_closes_connection() {
+        tokio::time::pause();
+        let router = serve_router();
+        let config_source = File::with_name("config");
+        let settings: Settings = Config::builder()
+            .add_source(config_source)
+            .set_override("parsing_timeout", 5)
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let (mut client, mut server_stream) = duplex(4096);
+
+        client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: loc")
+            .await
+            .unwrap();
+
+        let keep_alive = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            1,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        drop(server_stream);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+
+        assert!(matches!(keep_alive, ConnectionState::Close));
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(response.contains("connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn configured_error_page_replaces_builtin_page() {
+        let page = std::env::temp_dir().join(format!("httpserver-400-{}.html", std::process::id()));
+        std::fs::write(&page, "<h1>Custom bad request</h1>").unwrap();
+        let router = serve_router();
+        let mut settings = test_settings();
+        settings.error_pages =
+            HashMap::from([("400".to_string(), page.to_string_lossy().into_owned())]);
+        let shared = SharedState::new(&settings);
+        std::fs::remove_file(&page).unwrap();
+        let (mut client, mut server_stream) = duplex(4096);
+        client.write_all(b"GET / HTTP/9.9\r\n\r\n").await.unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &shared,
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::BadRequest);
+        assert_eq!(response.body, b"<h1>Custom bad request</h1>");
+        assert_eq!(response.headers.get("connection"), Some("close"));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::panic)]
+    async fn configured_500_page_is_served_on_handler_panic() {
+        let page = std::env::temp_dir().join(format!("httpserver-500-{}.html", std::process::id()));
+        std::fs::write(&page, "<h1>Custom server error</h1>").unwrap();
+        let mut router = serve_router();
+        router.route("/boom", |_req| async { panic!("boom") });
+        let mut settings = test_settings();
+        settings.error_pages =
+            HashMap::from([("500".to_string(), page.to_string_lossy().into_owned())]);
+        let shared = SharedState::new(&settings);
+        std::fs::remove_file(&page).unwrap();
+        let (mut client, mut server_stream) = duplex(4096);
+        client
+            .write_all(b"GET /boom HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &shared,
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::InternalServerError);
+        assert_eq!(response.body, b"<h1>Custom server error</h1>");
+        assert!(response.headers.get("x-request-id").is_some());
+    }
+
+    #[tokio::test]
+    async fn request_hook_observes_every_request() {
+        const REQUESTS: usize = 5;
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let hook_observed = Arc::clone(&observed);
+        router.on_request(move |request_line, status, _duration| {
+            hook_observed
+                .lock()
+                .unwrap()
+                .push((request_line.request_target.clone(), status));
+        });
+        let settings = test_settings();
+        let shared = SharedState::new(&settings);
+
+        for target in ["/test", "/missing"].iter().cycle().take(REQUESTS) {
+            let (mut client, mut server_stream) = duplex(4096);
+            let request = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            client.write_all(request.as_bytes()).await.unwrap();
+            process_request(
+                &mut server_stream,
+                &router,
+                &settings,
+                &shared,
+                &RequestContext::default(),
+                5,
+                &mut Vec::new(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let observed = observed.lock().unwrap().clone();
+        assert_eq!(observed.len(), REQUESTS);
+        assert_eq!(observed[0], ("/test".to_string(), StatusCode::Ok));
+        assert_eq!(observed[1], ("/missing".to_string(), StatusCode::NotFound));
+    }
+
+    #[tokio::test]
+    async fn request_hook_observes_rejected_requests() {
+        let mut router = serve_router();
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let hook_observed = Arc::clone(&observed);
+        router.on_request(move |request_line, status, _duration| {
+            hook_observed
+                .lock()
+                .unwrap()
+                .push((request_line.request_target.clone(), status));
+        });
+        let settings = test_settings();
+        let shared = SharedState::new(&settings);
+        let requests: [&[u8]; 3] = [
+            b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 999999999999\r\n\r\n",
+            b"GET /broken HTTP/1.1\r\nHost: localhost\r\nno colon\r\n\r\n",
+            b"GET / HTTP/9.9\r\n\r\n",
+        ];
+
+        for request in requests {
+            let (mut client, mut server_stream) = duplex(4096);
+            client.write_all(request).await.unwrap();
+            process_request(
+                &mut server_stream,
+                &router,
+                &settings,
+                &shared,
+                &RequestContext::default(),
+                5,
+                &mut Vec::new(),
+            )
+            .await
+            .unwrap();
+        }
+
+        // The last request line could not be parsed, so there is nothing to report it with.
+        assert_eq!(
+            observed.lock().unwrap().clone(),
+            [
+                ("/upload".to_string(), StatusCode::ContentTooLarge),
+                ("/broken".to_string(), StatusCode::BadRequest),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_request_id_is_logged_and_sent() {
+        let router = serve_router();
+        let settings = test_settings();
+        let sink = BytewiseSink::default();
+        let shared = SharedState {
+            error_log: ErrorLog::new(sink.clone()),
+            ..SharedState::new(&settings)
+        };
+        let (mut client, mut server_stream) = duplex(4096);
+        client.write_all(b"GET / HTTP/9.9\r\n\r\n").await.unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &shared,
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while !sink.0.lock().unwrap().ends_with(b"\n") {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Error was not logged");
+
+        assert_eq!(response.status, StatusCode::BadRequest);
+        let request_id = response.headers.get("x-request-id").unwrap();
+        let log = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            log,
+            format!("[{request_id}] unsupported HTTP version: 9.9\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_alive_header_is_sent_when_enabled() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<html><body>ok</body></html>")
+        });
+        let config_source = File::with_name("config");
+        let settings: Settings = Config::builder()
+            .add_source(config_source)
+            .set_override("keep_alive_header", true)
+            .unwrap()
+            .set_override("keep_alive_timeout", 15)
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let (client, mut server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+
+        client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let keep_alive = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = read_response(&mut client).await;
+
+        assert!(matches!(keep_alive, ConnectionState::KeepOpen));
+        assert!(response.contains("keep-alive: timeout=15, max=4\r\n"));
+    }
+
+    #[tokio::test]
+    async fn http_request_is_redirected_to_https_with_308() {
+        let mut settings = test_settings();
+        settings.https_redirect_port = 8443;
+        let (mut client, server) = duplex(4096);
+
+        let shared = SharedState::new(&settings);
+        let server_task =
+            tokio::spawn(async move { handle_redirect(server, &settings, &shared).await });
+
+        client
+            .write_all(b"GET /coffee?milk=oat HTTP/1.1\r\nHost: localhost:8080\r\n\r\n")
+            .await
+            .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::PermanentRedirect);
+        assert_eq!(
+            response.headers.get("location"),
+            Some("https://localhost:8443/coffee?milk=oat")
+        );
+        assert_eq!(response.headers.get("connection"), Some("close"));
+        server_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_http_request_is_rejected_with_request_id() {
+        let settings = test_settings();
+        let shared = SharedState::new(&settings);
+        let (mut client, server) = duplex(4096);
+
+        let server_task =
+            tokio::spawn(async move { handle_redirect(server, &settings, &shared).await });
+
+        client.write_all(b"GET / HTTP/9.9\r\n\r\n").await.unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::BadRequest);
+        assert!(response.headers.get("x-request-id").is_some());
+        assert_eq!(response.headers.get("connection"), Some("close"));
+        server_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_bodies_exceeding_memory_budget_are_rejected_with_503() {
+        let mut router = serve_router();
+        router.route("/upload", |_req| async {
+            sleep(Duration::from_millis(200)).await;
+            html_response(StatusCode::Ok, "<h1>Stored</h1>")
+        });
+        let router = Arc::new(router);
+        let settings = Arc::new(test_settings());
+        let shared = SharedState {
+            memory: MemoryBudget::new(1000),
+            ..SharedState::new(&settings)
+        };
+
+        let mut tasks = Vec::new();
+        for _ in 0..3 {
+            let (mut client, mut server_stream) = duplex(4096);
+            let router = Arc::clone(&router);
+            let settings = Arc::clone(&settings);
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                process_request(
+                    &mut server_stream,
+                    &router,
+                    &settings,
+                    &shared,
+                    &RequestContext::default(),
+                    1,
+                    &mut Vec::new(),
+                )
+                .await
+            });
+            tasks.push(tokio::spawn(async move {
+                let request = format!(
+                    "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 400\r\n\r\n{}",
+                    "x".repeat(400)
+                );
+                client.write_all(request.as_bytes()).await.unwrap();
+                response_from_reader(&mut client).await.unwrap().status
+            }));
+        }
+
+        let mut statuses = Vec::new();
+        for task in tasks {
+            statuses.push(task.await.unwrap() as u16);
+        }
+        statuses.sort_unstable();
+
+        assert_eq!(statuses, [200, 200, 503]);
+        assert_eq!(shared.memory.in_use(), 0);
+    }
+
+    /// Sink storing everything written to it, accepting a single byte per write so unsynchronized writers would interleave.
+    #[derive(Clone, Default)]
+    struct BytewiseSink(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for BytewiseSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let Some(byte) = buf.first() else {
+                return Ok(0);
+            };
+            self.0.lock().unwrap().push(*byte);
+            std::thread::yield_now();
+            Ok(1)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn access_log_lines_of_concurrent_requests_are_not_interleaved() {
+        const REQUESTS: usize = 50;
+        let mut router = serve_router();
+        router.route_method(Method::Get, "/items/:id", |_req| async {
+            html_response(StatusCode::Ok, "<h1>Item</h1>")
+        });
+        let router = Arc::new(router);
+        let settings = Arc::new(test_settings());
+        let sink = BytewiseSink::default();
+        let shared = SharedState {
+            access_log: Some(AccessLog::new(sink.clone())),
+            ..SharedState::new(&settings)
+        };
+
+        let mut tasks = Vec::new();
+        for id in 0..REQUESTS {
+            let (mut client, mut server_stream) = duplex(4096);
+            let router = Arc::clone(&router);
+            let settings = Arc::clone(&settings);
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                process_request(
+                    &mut server_stream,
+                    &router,
+                    &settings,
+                    &shared,
+                    &RequestContext::default(),
+                    1,
+                    &mut Vec::new(),
+                )
+                .await
+            });
+            tasks.push(tokio::spawn(async move {
+                let request = format!("GET /items/{id} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+                client.write_all(request.as_bytes()).await.unwrap();
+                response_from_reader(&mut client).await.unwrap().status
+            }));
+        }
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), StatusCode::Ok);
+        }
+        timeout(Duration::from_secs(5), async {
+            // Splitting at line breaks yields one more element than there are complete lines.
+            while sink.0.lock().unwrap().split(|&b| b == b'\n').count() <= REQUESTS {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Access log was not written");
+
+        let log = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let mut ids: Vec<usize> = log
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split(' ').collect();
+                assert_eq!(fields.len(), 4, "{line}");
+                assert_eq!(fields[0], "GET", "{line}");
+                assert_eq!(fields[2], "200", "{line}");
+                let duration = fields[3].strip_suffix("ms").unwrap();
+                assert!(duration.parse::<f64>().is_ok(), "{line}");
+                fields[1].strip_prefix("/items/").unwrap().parse().unwrap()
+            })
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..REQUESTS).collect::<Vec<_>>());
+    }
+
+    /// Helper function sending concurrent requests to a slow handler with a limit on in-flight requests, returning the sorted statuses.
+    async fn inflight_statuses(limit: usize, queue_timeout: Duration, requests: usize) -> Vec<u16> {
+        let mut router = serve_router();
+        router.route("/slow", |_req| async {
+            sleep(Duration::from_secs(1)).await;
+            html_response(StatusCode::Ok, "<h1>Done</h1>")
+        });
+        let router = Arc::new(router);
+        let settings = Arc::new(test_settings());
+        let shared = SharedState {
+            inflight: InflightLimiter::new(limit, queue_timeout),
+            ..SharedState::new(&settings)
+        };
+
+        let mut tasks = Vec::new();
+        for _ in 0..requests {
+            let (mut client, mut server_stream) = duplex(4096);
+            let router = Arc::clone(&router);
+            let settings = Arc::clone(&settings);
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                process_request(
+                    &mut server_stream,
+                    &router,
+                    &settings,
+                    &shared,
+                    &RequestContext::default(),
+                    1,
+                    &mut Vec::new(),
+                )
+                .await
+            });
+            tasks.push(tokio::spawn(async move {
+                client
+                    .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                    .await
+                    .unwrap();
+                response_from_reader(&mut client).await.unwrap().status
+            }));
+        }
+
+        let mut statuses = Vec::new();
+        for task in tasks {
+            statuses.push(task.await.unwrap() as u16);
+        }
+        statuses.sort_unstable();
+        assert_eq!(shared.inflight.available(), limit);
+        statuses
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_exceeding_inflight_limit_are_rejected_with_503() {
+        let statuses = inflight_statuses(2, Duration::ZERO, 5).await;
+
+        assert_eq!(statuses, [200, 200, 503, 503, 503]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_exceeding_inflight_limit_are_queued() {
+        let start = Instant::now();
+        let statuses = inflight_statuses(2, Duration::from_secs(10), 5).await;
+
+        assert_eq!(statuses, [200; 5]);
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+
+    /// Helper function processing a single request on a route that optionally closes the connection.
+    async fn keep_alive_decision(request: &str, handler_closes: bool) -> (bool, String) {
+        let mut router = serve_router();
+        router.route("/test", move |_req| async move {
+            let mut response = html_response(StatusCode::Ok, "<h1>All good!</h1>");
+            if handler_closes {
+                response.headers.insert("Connection", "close");
+            }
+            response
+        });
+        let settings = test_settings();
+        let (client, mut server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let keep_open = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        (
+            matches!(keep_open, ConnectionState::KeepOpen),
+            read_response(&mut client).await,
+        )
+    }
+
+    #[tokio::test]
+    async fn handler_close_closes_connection() {
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let (keep_open, response) = keep_alive_decision(request, true).await;
+
+        assert!(!keep_open);
+        assert_eq!(response.matches("onnection: close").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn client_close_closes_connection() {
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\nConnection: Close\r\n\r\n";
+
+        let (keep_open, response) = keep_alive_decision(request, false).await;
+
+        assert!(!keep_open);
+        assert!(response.contains("connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn connection_stays_open_when_both_sides_keep_alive() {
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n";
+
+        let (keep_open, response) = keep_alive_decision(request, false).await;
+
+        assert!(keep_open);
+        assert!(!response.contains("close"));
+        assert!(response.contains("connection: keep-alive\r\n"));
+    }
+
+    #[tokio::test]
+    async fn default_client_connection_stays_open() {
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let (keep_open, response) = keep_alive_decision(request, false).await;
+
+        assert!(keep_open);
+        assert!(response.contains("connection: keep-alive\r\n"));
+    }
+
+    #[tokio::test]
+    async fn configured_headers_are_stripped_before_handler() {
+        let mut router = serve_router();
+        router.route("/test", |req| async move {
+            let forwarded = req.headers.get("x-forwarded-for").unwrap_or("none");
+            html_response(StatusCode::Ok, forwarded)
+        });
+        let mut settings = test_settings();
+        settings.strip_request_headers = vec!["X-Forwarded-For".to_string()];
+        let (client, mut server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 10.0.0.1\r\n\r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = read_response(&mut client).await;
+
+        assert!(response.ends_with("\r\n\r\nnone"));
+    }
+
+    /// Helper function sending a websocket upgrade request to a non-upgrading handler, returning the response and whether the connection stays open.
+    async fn upgrade_request(upgrade_policy: UpgradePolicy) -> (String, bool) {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let mut settings = test_settings();
+        settings.upgrade_policy = upgrade_policy;
+        let (client, mut server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let keep_open = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        (
+            read_response(&mut client).await,
+            matches!(keep_open, ConnectionState::KeepOpen),
+        )
+    }
+
+    #[tokio::test]
+    async fn ignored_upgrade_is_answered_normally() {
+        let (response, keep_open) = upgrade_request(UpgradePolicy::Ignore).await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!response.contains("upgrade"));
+        assert!(keep_open);
+    }
+
+    #[tokio::test]
+    async fn rejected_upgrade_is_not_implemented() {
+        let (response, keep_open) = upgrade_request(UpgradePolicy::Reject).await;
+
+        assert!(response.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+        assert!(keep_open);
+    }
+
+    #[tokio::test]
+    async fn plain_connection_is_not_secure() {
+        let mut router = serve_router();
+        router.route("/test", |req| async move {
+            html_response(StatusCode::Ok, &req.context.is_secure.to_string())
+        });
+        let settings = test_settings();
+        let (client, mut server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+        client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = read_response(&mut client).await;
+
+        assert!(response.ends_with("\r\n\r\nfalse"));
+    }
+
+    #[tokio::test]
+    async fn server_timing_header_reports_handler_duration() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            sleep(Duration::from_millis(20)).await;
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let mut settings = test_settings();
+        settings.server_timing_header = true;
+        let (mut client, mut server_stream) = duplex(4096);
+        client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        let timing = response.headers.get("server-timing").unwrap();
+        let duration: f64 = timing.strip_prefix("app;dur=").unwrap().parse().unwrap();
+        assert!((20.0..10_000.0).contains(&duration));
+    }
+
+    #[tokio::test]
+    async fn enabled_compression_gzips_accepted_responses() {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, &"<p>compress me</p>".repeat(100))
+        });
+        let mut settings = test_settings();
+        settings.compression.enabled = true;
+        let (mut client, mut server_stream) = duplex(8192);
+        client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: br;q=1.0, gzip;q=0.8\r\n\r\n")
+            .await
+            .unwrap();
+
+        process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert_eq!(response.headers.get("content-encoding"), Some("gzip"));
+        assert_eq!(response.headers.get("vary"), Some("Accept-Encoding"));
+        let mut decoded = String::new();
+        io::Read::read_to_string(&mut GzDecoder::new(response.body.as_slice()), &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "<p>compress me</p>".repeat(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_and_read_timeouts_fire_at_configured_durations() {
+        let router = serve_router();
+        let mut settings = test_settings();
+        settings.keep_alive_timeout = 15;
+        settings.parsing_timeout = 30;
+        let shared = SharedState::new(&settings);
+
+        let (mut idle_client, mut idle_stream) = duplex(4096);
+        let start = Instant::now();
+        let keep_alive = process_request(
+            &mut idle_stream,
+            &router,
+            &settings,
+            &shared,
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let idle_response = response_from_reader(&mut idle_client).await.unwrap();
+
+        assert!(matches!(keep_alive, ConnectionState::Close));
+        assert_eq!(start.elapsed(), Duration::from_secs(15));
+        assert_eq!(idle_response.status, StatusCode::RequestTimeout);
+        assert!(idle_response.headers.get("x-request-id").is_some());
+
+        settings.parsing_timeout = 5;
+        let (mut slow_client, mut slow_stream) = duplex(4096);
+        slow_client
+            .write_all(b"GET /test HTTP/1.1\r\nHost: loc")
+            .await
+            .unwrap();
+        let start = Instant::now();
+        let keep_alive = process_request(
+            &mut slow_stream,
+            &router,
+            &settings,
+            &shared,
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let slow_response = response_from_reader(&mut slow_client).await.unwrap();
+
+        assert!(matches!(keep_alive, ConnectionState::Close));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(slow_response.status, StatusCode::RequestTimeout);
+    }
+
+    #[test]
+    fn default_parse_limits_match_config() {
+        assert_eq!(test_settings().parse_limits(), ParseLimits::default());
+    }
+
+    /// Helper stream counting how often it is flushed.
+    struct FlushCounter {
+        inner: tokio::io::DuplexStream,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl AsyncRead for FlushCounter {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for FlushCounter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn back_to_back_requests_with_bodies_keep_their_bytes() {
+        let mut router = serve_router();
+        router.route("/echo", |req| async move {
+            Response::builder().body(req.body).build()
+        });
+        let settings = test_settings();
+        let (client, server_stream) = duplex(4096);
+        let mut client = BufReader::new(client);
+
+        // Both requests arrive in one read, so the start of the second one is buffered while parsing the first.
+        let first = "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nfirst";
+        let second = "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecond";
+        client
+            .write_all(format!("{first}{second}").as_bytes())
+            .await
+            .unwrap();
+
+        handle(
+            server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+        )
+        .await
+        .unwrap();
+
+        let first_response = read_response(&mut client).await;
+        let second_response = read_response(&mut client).await;
+        assert!(first_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(first_response.ends_with("\r\n\r\nfirst"));
+        assert!(second_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(second_response.ends_with("\r\n\r\nsecond"));
+    }
+
+    /// Helper function sending three pipelined requests in a single write, returning the responses and the amount of flushes.
+    async fn pipelined_flushes(flush_strategy: FlushStrategy) -> (Vec<String>, usize) {
+        let mut router = serve_router();
+        router.route("/test", |_req| async {
+            html_response(StatusCode::Ok, "<h1>All good!</h1>")
+        });
+        let mut settings = test_settings();
+        settings.flush_strategy = flush_strategy;
+        let (client, server_stream) = duplex(4096);
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let server_stream = FlushCounter {
+            inner: server_stream,
+            flushes: Arc::clone(&flushes),
+        };
+        let mut client = BufReader::new(client);
+
+        let request = "GET /test HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let last = "GET /test HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        client
+            .write_all(format!("{request}{request}{last}").as_bytes())
+            .await
+            .unwrap();
+
+        handle(
+            server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            responses.push(read_response(&mut client).await);
+        }
+        (responses, flushes.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn coalesced_flushes_batch_pipelined_responses() {
+        let (_, immediate_flushes) = pipelined_flushes(FlushStrategy::Immediate).await;
+        let (responses, coalesced_flushes) = pipelined_flushes(FlushStrategy::Coalesce).await;
+
+        assert_eq!(immediate_flushes, 3);
+        assert!(coalesced_flushes < immediate_flushes);
+        assert_eq!(responses.len(), 3);
+        for response in &responses {
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.ends_with("<h1>All good!</h1>"));
+        }
+    }
+
+    #[tokio::test]
+    async fn connect_tunnel_relays_bytes_both_ways() {
+        let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = echo.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut upstream, _) = echo.accept().await.unwrap();
+            let (mut reader, mut writer) = upstream.split();
+            tokio::io::copy(&mut reader, &mut writer).await.unwrap();
+        });
+
+        let mut router = serve_router();
+        let allowed = authority.clone();
+        router.allow_connect(move |requested| requested == allowed);
+        let settings = test_settings();
+        let (client, server_stream) = duplex(4096);
+        let server = tokio::spawn(async move {
+            handle(
+                server_stream,
+                &router,
+                &settings,
+                &SharedState::new(&settings),
+                &RequestContext::default(),
+            )
+            .await
+        });
+
+        let mut client = BufReader::new(client);
+        client
+            .write_all(
+                format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\r\nping").as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut status_line = String::new();
+        client.read_line(&mut status_line).await.unwrap();
+        let mut blank = String::new();
+        client.read_line(&mut blank).await.unwrap();
+        assert_eq!(status_line, "HTTP/1.1 200 Connection Established\r\n");
+        assert_eq!(blank, "\r\n");
+
+        let mut echoed = [0u8; 4];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+        client.write_all(b"pong").await.unwrap();
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"pong");
+
+        client.shutdown().await.unwrap();
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreachable_connect_authority_is_answered_with_tagged_502() {
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = closed.local_addr().unwrap().to_string();
+        drop(closed);
+
+        let mut router = serve_router();
+        let allowed = authority.clone();
+        router.allow_connect(move |requested| requested == allowed);
+        let settings = test_settings();
+        let (mut client, mut server_stream) = duplex(4096);
+        client
+            .write_all(
+                format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\r\n").as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let state = process_request(
+            &mut server_stream,
+            &router,
+            &settings,
+            &SharedState::new(&settings),
+            &RequestContext::default(),
+            5,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+        let response = response_from_reader(&mut client).await.unwrap();
+
+        assert!(matches!(state, ConnectionState::Close));
+        assert_eq!(response.status, StatusCode::BadGateway);
+        assert!(response.headers.get("x-request-id").is_some());
+    }
+
+    #[tokio::test]
+    async fn connect_tunnel_is_closed_after_tunnel_timeout() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = upstream.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            // Keeps the tunnel open without sending anything.
+            let (_upstream, _) = upstream.accept().await.unwrap();
+            sleep(Duration::from_secs(30)).await;
+        });
+
+        let mut router = serve_router();
+        let allowed = authority.clone();
+        router.allow_connect(move |requested| requested == allowed);
+        let mut settings = test_settings();
+        settings.tunnel_timeout = 1;
+        let (client, server_stream) = duplex(4096);
+        let server = tokio::spawn(async move {
+            handle(
+                server_stream,
+                &router,
+                &settings,
+                &SharedState::new(&settings),
+                &RequestContext::default(),
+            )
+            .await
+        });
+
+        let mut client = BufReader::new(client);
+        client
+            .write_all(
+                format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\r\n").as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut status_line = String::new();
+        client.read_line(&mut status_line).await.unwrap();
+        assert_eq!(status_line, "HTTP/1.1 200 Connection Established\r\n");
+
+        timeout(Duration::from_secs(5), server)
+            .await
+            .expect("Tunnel outlived its timeout")
+            .unwrap()
+            .unwrap();
+    }
 }
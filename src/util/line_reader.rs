@@ -0,0 +1,101 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Wraps a reader and yields complete lines, regardless of how the data is split across reads.
+///
+/// Lines may be terminated by either `\r\n` or `\n`, the terminator is not part of the returned line.
+#[derive(Debug)]
+pub struct LineReader<R> {
+    /// The wrapped reader.
+    reader: R,
+    /// Data that was read but not yet returned as a line.
+    buffer: Vec<u8>,
+    /// Whether the wrapped reader reached its end.
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    /// Creates a new `LineReader` wrapping the passed reader.
+    pub const fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Reads the next complete line.
+    ///
+    /// Returns the remaining data as a final line if the reader ends without a trailing newline, and `None` once everything was returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if reading from the underlying reader fails.
+    pub async fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut temp = [0u8; 64];
+        loop {
+            if let Some(position) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                let mut line: Vec<u8> = self.buffer.drain(..=position).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+            }
+
+            if self.eof {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                let line = std::mem::take(&mut self.buffer);
+                return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+            }
+
+            let read = self.reader.read(&mut temp).await?;
+            if read == 0 {
+                self.eof = true;
+            } else {
+                self.buffer.extend_from_slice(&temp[..read]);
+            }
+        }
+    }
+
+    /// Returns the wrapped reader, discarding any buffered data.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::io::Builder;
+
+    use crate::util::line_reader::LineReader;
+
+    #[tokio::test]
+    async fn lines_split_across_reads_are_joined() {
+        let reader = Builder::new()
+            .read(b"GET / HT")
+            .read(b"TP/1.1\r")
+            .read(b"\nHost: local")
+            .read(b"host\n\r\n")
+            .build();
+        let mut lines = LineReader::new(reader);
+
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "GET / HTTP/1.1");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "Host: localhost");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "");
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn final_line_without_newline_is_returned() {
+        let reader = Builder::new().read(b"first\r\nsec").read(b"ond").build();
+        let mut lines = LineReader::new(reader);
+
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "first");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "second");
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,2 @@
+/// Module containing a reader yielding complete lines
+pub mod line_reader;